@@ -2,11 +2,14 @@
  *
  * IPC memory must be in lower RAM for modem access.
  * This layout matches the official Embassy nrf9160 modem example.
+ *
+ * The last 4K flash page (0x000FF000) is reserved for the device
+ * configuration record (see src/config.rs).
  */
 
 MEMORY
 {
-    FLASH : ORIGIN = 0x00000000, LENGTH = 1024K
+    FLASH : ORIGIN = 0x00000000, LENGTH = 1020K
     IPC   : ORIGIN = 0x20000000, LENGTH = 64K
     RAM   : ORIGIN = 0x20010000, LENGTH = 192K
 }
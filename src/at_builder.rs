@@ -0,0 +1,102 @@
+//! Builder for AT commands with parameters.
+//!
+//! Formatting commands by hand with `write!` makes it easy to drop a
+//! separator or a quote, or to pass a value (an APN password, say) that
+//! breaks the command. `AtCommandBuilder` appends parameters with the
+//! separators the modem expects, `=` before the first and `,` between the
+//! rest, and quotes string parameters:
+//!
+//! ```ignore
+//! let cmd = AtCommandBuilder::<64>::new("AT+CGDCONT")?
+//!     .num(0u8)?
+//!     .string("IP")?
+//!     .string(apn)?
+//!     .build();
+//! ```
+//!
+//! ## Quoting
+//! The modem has no escape sequence for `"` within a string parameter, and
+//! a CR or LF ends the command early. Strings containing them are rejected
+//! instead of being sent as a malformed command.
+//!
+//! ## Error Handling
+//! Methods return `Error::Config` if the command does not fit in the
+//! builder's `N` bytes or a string parameter cannot be quoted.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+
+use core::fmt::Write as _;
+
+use heapless::String;
+
+/// AT command under construction, at most `N` bytes long.
+#[derive(Clone, Debug)]
+pub struct AtCommandBuilder<const N: usize> {
+	cmd: String<N>,
+	has_params: bool,
+}
+
+impl<const N: usize> AtCommandBuilder<N> {
+	/// Start a command, e.g. `AT+CGAUTH`.
+	///
+	/// # Errors
+	/// Returns `Error::Config` if `command` does not fit.
+	pub fn new(command: &str) -> Result<Self> {
+		Ok(Self {
+			cmd: String::try_from(command).map_err(|_| Error::Config)?,
+			has_params: false,
+		})
+	}
+
+	/// Write the separator before the next parameter.
+	fn separator(&mut self) -> Result<()> {
+		let separator = if self.has_params { ',' } else { '=' };
+		self.has_params = true;
+		self.cmd.push(separator).map_err(|_| Error::Config)
+	}
+
+	/// Append a numeric parameter.
+	///
+	/// # Errors
+	/// Returns `Error::Config` if the command gets too long.
+	pub fn num(mut self, value: impl Into<u32>) -> Result<Self> {
+		self.separator()?;
+		write!(self.cmd, "{}", value.into()).map_err(|_| Error::Config)?;
+		Ok(self)
+	}
+
+	/// Append a quoted string parameter.
+	///
+	/// # Errors
+	/// Returns `Error::Config` if `value` contains `"`, CR or LF, or the
+	/// command gets too long.
+	pub fn string(mut self, value: &str) -> Result<Self> {
+		if value.contains(['"', '\r', '\n']) {
+			return Err(Error::Config);
+		}
+		self.separator()?;
+		write!(self.cmd, "\"{}\"", value).map_err(|_| Error::Config)?;
+		Ok(self)
+	}
+
+	/// Append an omitted parameter, keeping the positions of later ones.
+	///
+	/// # Errors
+	/// Returns `Error::Config` if the command gets too long.
+	pub fn empty(mut self) -> Result<Self> {
+		self.separator()?;
+		Ok(self)
+	}
+
+	/// Get the command built so far.
+	pub fn as_str(&self) -> &str {
+		&self.cmd
+	}
+
+	/// Finish the command.
+	pub fn build(self) -> String<N> {
+		self.cmd
+	}
+}
@@ -0,0 +1,276 @@
+//! Serialized AT command queue.
+//!
+//! Several tasks send AT commands (registration monitor, reports, the
+//! console). To keep each response matched to its command regardless of
+//! the driver's locking, `at_queue_task` is the only user of `Control`
+//! once started: callers put requests on a channel and wait for their own
+//! reply. `modem::at_command_bytes()`, which the other AT helpers build
+//! on, goes through the queue as soon as it runs.
+//!
+//! ## Long Commands
+//! Requests are copied into the channel, so the queue only carries commands
+//! up to `AT_CMD_MAX` bytes. Longer ones (credential writes with PEM
+//! payloads) are sent with `at_command_held()`, which holds the queue while
+//! the command runs: the queue task takes the same lock around every
+//! command, so the two never talk to the modem at the same time.
+//!
+//! ## Response Buffers
+//! Responses are written into a pool of `POOL_COUNT` buffers of
+//! `RESP_BUF_SIZE` bytes, so RAM use is bounded no matter how many tasks
+//! queue up. Each caller holds one pool buffer (its slot) from sending the
+//! command until it drops the `AtResponse` returned by `request()`, so at
+//! most `POOL_COUNT` commands are pending at a time; further callers wait
+//! for a buffer. Replies carry a sequence number: a caller that gave up
+//! (e.g. on a timeout) returns its buffer, and the late reply is ignored by
+//! the buffer's next user.
+//!
+//! `modem::at_response()` hands the `AtResponse` to the AT helpers, so
+//! they parse the pool buffer instead of copying the response to their own
+//! stack.
+//!
+//! ## Error Handling
+//! `request()` and `at_command()` return `Error::Config` if the command
+//! does not fit in `AT_CMD_MAX`; use `at_command_held()` for those.
+//! Responses longer than `RESP_BUF_SIZE` are truncated.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+use crate::logger::AT_RESP_BUF_SIZE;
+
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use embassy_executor::Spawner;
+use embassy_net_nrf91::Control;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+use heapless::Vec;
+
+/// Longest command accepted by the queue.
+pub const AT_CMD_MAX: usize = 256;
+
+/// Size of each pooled response buffer.
+pub const RESP_BUF_SIZE: usize = AT_RESP_BUF_SIZE;
+
+/// Number of pooled response buffers, i.e. commands that can be pending
+/// at once.
+pub const POOL_COUNT: usize = 4;
+
+/// Command waiting to be sent.
+struct AtRequest {
+	cmd: Vec<u8, AT_CMD_MAX>,
+	slot: usize,
+	seq: u32,
+}
+
+/// Response buffers, indexed by slot.
+///
+/// A buffer is written only by the queue task while a request for its
+/// slot is in flight, and read only by the slot holder after its own reply
+/// arrived. Requests are handled in order, so once the holder's reply is
+/// in no earlier request for the slot is left, and no later one can be
+/// queued until the holder returns the slot.
+struct BufferPool {
+	bufs: [UnsafeCell<[u8; RESP_BUF_SIZE]>; POOL_COUNT],
+}
+
+// Access is coordinated through the slots, see above
+unsafe impl Sync for BufferPool {}
+
+static POOL: BufferPool = BufferPool {
+	bufs: [const { UnsafeCell::new([0u8; RESP_BUF_SIZE]) }; POOL_COUNT],
+};
+
+/// Commands waiting for the queue task.
+static REQUESTS: Channel<CriticalSectionRawMutex, AtRequest, POOL_COUNT> = Channel::new();
+
+/// Indices of free slots.
+static FREE_SLOTS: Channel<CriticalSectionRawMutex, usize, POOL_COUNT> = Channel::new();
+
+/// Sequence number and response length per slot.
+static REPLIES: [Signal<CriticalSectionRawMutex, (u32, usize)>; POOL_COUNT] =
+	[const { Signal::new() }; POOL_COUNT];
+
+/// Sequence number of the next request.
+static NEXT_SEQ: AtomicU32 = AtomicU32::new(0);
+
+/// Held while a command is in flight, by the queue task or by
+/// `at_command_held()`.
+static MODEM_LOCK: Mutex<CriticalSectionRawMutex, ()> = Mutex::new(());
+
+// Set once the queue task runs
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Set once the free slots have been queued
+static POOL_FILLED: AtomicBool = AtomicBool::new(false);
+
+/// Put all slots on the free list, once.
+fn fill_pool() {
+	if !POOL_FILLED.swap(true, Ordering::AcqRel) {
+		for slot in 0..POOL_COUNT {
+			let _ = FREE_SLOTS.try_send(slot);
+		}
+	}
+}
+
+/// Task sending queued AT commands one at a time.
+#[embassy_executor::task]
+pub async fn at_queue_task(control: &'static Control<'static>) -> ! {
+	loop {
+		let request = REQUESTS.receive().await;
+
+		// SAFETY: only this task writes the buffer, and its holder does not
+		// read it before the reply below
+		let resp_buf = unsafe { &mut *POOL.bufs[request.slot].get() };
+		let len = {
+			let _held = MODEM_LOCK.lock().await;
+			control.at_command(&request.cmd, resp_buf).await
+		};
+
+		REPLIES[request.slot].signal((request.seq, len));
+	}
+}
+
+/// Start the AT command queue.
+///
+/// From here on all AT commands sent through `modem::at_command_bytes()`
+/// are serialized.
+///
+/// # Errors
+/// Returns `Error::TaskSpawn` if the queue task cannot be spawned.
+pub fn start(spawner: &Spawner, control: &'static Control<'static>) -> Result<()> {
+	fill_pool();
+
+	let token = at_queue_task(control).map_err(|_| Error::TaskSpawn)?;
+	spawner.spawn(token);
+	RUNNING.store(true, Ordering::Release);
+	Ok(())
+}
+
+/// Check whether the queue task has been started.
+pub fn is_running() -> bool {
+	RUNNING.load(Ordering::Acquire)
+}
+
+/// Returns the slot to the free list when the caller is done, or drops
+/// the request early.
+struct SlotGuard(usize);
+
+impl Drop for SlotGuard {
+	fn drop(&mut self) {
+		let _ = FREE_SLOTS.try_send(self.0);
+	}
+}
+
+/// Response to a queued AT command, held in a pool buffer.
+///
+/// Derefs to the response bytes. Dropping it returns the buffer to the
+/// pool, so keep it only as long as needed.
+pub struct AtResponse {
+	slot: SlotGuard,
+	len: usize,
+}
+
+impl Deref for AtResponse {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		// SAFETY: our reply arrived and we hold the slot, so the queue task
+		// no longer writes this buffer
+		let buf = unsafe { &*POOL.bufs[self.slot.0].get() };
+		&buf[..self.len]
+	}
+}
+
+/// Send an AT command through the queue and wait for its response.
+///
+/// Waits for a free pool buffer first if `POOL_COUNT` commands are
+/// already pending.
+///
+/// # Returns
+/// The response, in a pool buffer returned when it is dropped.
+///
+/// # Errors
+/// Returns `Error::Config` if `cmd` is longer than `AT_CMD_MAX`, or
+/// `Error::AtCommand` if the queue has not been started.
+pub async fn request(cmd: &[u8]) -> Result<AtResponse> {
+	if !is_running() {
+		return Err(Error::AtCommand);
+	}
+	let cmd = Vec::from_slice(cmd).map_err(|_| Error::Config)?;
+
+	let slot = SlotGuard(FREE_SLOTS.receive().await);
+	let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+	REQUESTS.send(AtRequest {
+		cmd,
+		slot: slot.0,
+		seq,
+	})
+	.await;
+
+	loop {
+		let (reply_seq, len) = REPLIES[slot.0].wait().await;
+		// Skip a late reply meant for a previous holder of this slot
+		if reply_seq == seq {
+			return Ok(AtResponse { slot, len });
+		}
+	}
+}
+
+/// Send an AT command, through the queue once it runs.
+///
+/// Before `start()` nothing else talks to the modem, so the command is sent
+/// on `control` directly, into a pool buffer all the same.
+///
+/// # Returns
+/// The response, in a pool buffer returned when it is dropped.
+///
+/// # Errors
+/// Returns `Error::Config` if the queue runs and `cmd` is longer than
+/// `AT_CMD_MAX`.
+pub async fn request_with(control: &Control<'_>, cmd: &[u8]) -> Result<AtResponse> {
+	if is_running() {
+		return request(cmd).await;
+	}
+
+	fill_pool();
+	let slot = SlotGuard(FREE_SLOTS.receive().await);
+	// SAFETY: without the queue task no request for the slot is in flight,
+	// and we hold it
+	let resp_buf = unsafe { &mut *POOL.bufs[slot.0].get() };
+	let len = control.at_command(cmd, resp_buf).await;
+	Ok(AtResponse { slot, len })
+}
+
+/// Send an AT command through the queue, copying the response to
+/// `resp_buf`.
+///
+/// # Returns
+/// Number of bytes written to `resp_buf`; longer responses are truncated.
+///
+/// # Errors
+/// Returns `Error::Config` if `cmd` is longer than `AT_CMD_MAX`.
+pub async fn at_command(cmd: &[u8], resp_buf: &mut [u8]) -> Result<usize> {
+	let response = request(cmd).await?;
+	let len = response.len().min(resp_buf.len());
+	resp_buf[..len].copy_from_slice(&response[..len]);
+	Ok(len)
+}
+
+/// Send an AT command too long for the queue, holding the queue while it
+/// runs.
+///
+/// Waits for the command in flight to finish, then sends `cmd` directly;
+/// queued commands wait until it is done. Works for commands of any length,
+/// but is meant for those longer than `AT_CMD_MAX`.
+///
+/// # Returns
+/// Number of bytes written to `resp_buf`.
+pub async fn at_command_held(control: &Control<'_>, cmd: &[u8], resp_buf: &mut [u8]) -> usize {
+	let _held = MODEM_LOCK.lock().await;
+	control.at_command(cmd, resp_buf).await
+}
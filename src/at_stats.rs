@@ -0,0 +1,86 @@
+//! AT command timing statistics.
+//!
+//! Compiled in with the `at-timing` feature. `modem::at_command_bytes()`
+//! records how long each command took, including time spent waiting in
+//! the AT queue, into a fixed set of duration buckets. `at_timing_stats()`
+//! returns the histogram together with the slowest command seen, which
+//! shows whether, say, `AT+CGACT` or `AT+COPS` dominates setup time.
+
+#![allow(dead_code)]
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Duration;
+use heapless::Vec;
+
+/// Upper bounds (exclusive, in ms) of the histogram buckets. A last bucket
+/// counts everything slower.
+pub const BUCKET_BOUNDS_MS: [u64; 6] = [10, 50, 100, 500, 1000, 5000];
+
+/// Number of histogram buckets.
+pub const BUCKET_COUNT: usize = BUCKET_BOUNDS_MS.len() + 1;
+
+/// Bytes of the slowest command kept for identification.
+pub const CMD_PREFIX_MAX: usize = 16;
+
+/// Accumulated AT command timings.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AtTimingStats {
+	/// Commands per duration bucket, see `BUCKET_BOUNDS_MS`
+	pub buckets: [u32; BUCKET_COUNT],
+	/// Total time spent in AT commands
+	pub total: Duration,
+	/// Duration of the slowest command
+	pub slowest: Duration,
+	/// Start of the slowest command, e.g. `AT+CGACT=1,0`
+	pub slowest_cmd: Vec<u8, CMD_PREFIX_MAX>,
+}
+
+impl AtTimingStats {
+	/// Number of commands recorded.
+	pub fn count(&self) -> u32 {
+		self.buckets.iter().sum()
+	}
+}
+
+static STATS: Mutex<CriticalSectionRawMutex, RefCell<AtTimingStats>> =
+	Mutex::new(RefCell::new(AtTimingStats {
+		buckets: [0; BUCKET_COUNT],
+		total: Duration::from_ticks(0),
+		slowest: Duration::from_ticks(0),
+		slowest_cmd: Vec::new(),
+	}));
+
+/// Record the duration of one command.
+pub fn record(cmd: &[u8], elapsed: Duration) {
+	let ms = elapsed.as_millis();
+	let bucket = BUCKET_BOUNDS_MS
+		.iter()
+		.position(|&bound| ms < bound)
+		.unwrap_or(BUCKET_COUNT - 1);
+
+	STATS.lock(|stats| {
+		let mut stats = stats.borrow_mut();
+		stats.buckets[bucket] = stats.buckets[bucket].saturating_add(1);
+		stats.total += elapsed;
+		if elapsed > stats.slowest {
+			stats.slowest = elapsed;
+			stats.slowest_cmd.clear();
+			let _ = stats
+				.slowest_cmd
+				.extend_from_slice(&cmd[..cmd.len().min(CMD_PREFIX_MAX)]);
+		}
+	});
+}
+
+/// Get a copy of the timing statistics.
+pub fn at_timing_stats() -> AtTimingStats {
+	STATS.lock(|stats| stats.borrow().clone())
+}
+
+/// Clear the timing statistics.
+pub fn reset_at_timing_stats() {
+	STATS.lock(|stats| *stats.borrow_mut() = AtTimingStats::default());
+}
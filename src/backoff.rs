@@ -0,0 +1,169 @@
+//! Exponential backoff for retry loops.
+//!
+//! `Backoff` produces a growing sequence of delays (`initial`, then times
+//! `multiplier` each attempt, capped at `max`), with optional random
+//! jitter so that a fleet of devices losing coverage together does not
+//! retry in lockstep. Jittered delays are capped at `max` as well.
+//!
+//! The nRF91 application core has no hardware RNG; jitter uses a small
+//! PRNG seeded by the caller, typically with `network::seed_from_imei`.
+
+#![allow(dead_code)]
+
+use embassy_time::Duration;
+
+/// Largest jitter accepted by `Backoff::with_jitter`, in percent.
+pub const MAX_JITTER_PERCENT: u8 = 50;
+
+/// Exponential backoff delay generator.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+	initial: Duration,
+	max: Duration,
+	multiplier: u32,
+	jitter_percent: u8,
+	current: Duration,
+	rng: u64,
+}
+
+impl Backoff {
+	/// Create a backoff doubling from `initial` up to `max`, without jitter.
+	pub const fn new(initial: Duration, max: Duration) -> Self {
+		Self {
+			initial,
+			max,
+			multiplier: 2,
+			jitter_percent: 0,
+			current: initial,
+			rng: 0,
+		}
+	}
+
+	/// Set the growth factor per attempt (at least 1).
+	pub const fn with_multiplier(mut self, multiplier: u32) -> Self {
+		self.multiplier = if multiplier == 0 { 1 } else { multiplier };
+		self
+	}
+
+	/// Randomize each delay by up to `percent` either way.
+	///
+	/// `percent` is capped at `MAX_JITTER_PERCENT`, so a delay never
+	/// shrinks to zero. `seed` should differ between devices, e.g.
+	/// `network::seed_from_imei`.
+	pub const fn with_jitter(mut self, percent: u8, seed: u64) -> Self {
+		self.jitter_percent = if percent > MAX_JITTER_PERCENT {
+			MAX_JITTER_PERCENT
+		} else {
+			percent
+		};
+		// xorshift state must not be zero
+		self.rng = seed | 1;
+		self
+	}
+
+	/// Get the delay before the next attempt and advance the sequence.
+	pub fn next_delay(&mut self) -> Duration {
+		let delay = self.current;
+
+		let next = self
+			.current
+			.as_ticks()
+			.saturating_mul(u64::from(self.multiplier));
+		self.current = Duration::from_ticks(next.min(self.max.as_ticks()));
+
+		self.jitter(delay).min(self.max)
+	}
+
+	/// Restart the sequence at the initial delay, e.g. after a success.
+	pub fn reset(&mut self) {
+		self.current = self.initial;
+	}
+
+	/// Apply the configured jitter to `delay`.
+	fn jitter(&mut self, delay: Duration) -> Duration {
+		if self.jitter_percent == 0 {
+			return delay;
+		}
+
+		// xorshift64*
+		self.rng ^= self.rng >> 12;
+		self.rng ^= self.rng << 25;
+		self.rng ^= self.rng >> 27;
+		let random = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32;
+
+		// Scale into [100 - jitter, 100 + jitter] percent
+		let span = 2 * u64::from(self.jitter_percent) + 1;
+		let percent = 100 - u64::from(self.jitter_percent) + random % span;
+		Duration::from_ticks(delay.as_ticks().saturating_mul(percent) / 100)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Durations are given in ticks so the results do not depend on the tick rate
+
+	fn delays(backoff: &mut Backoff, count: usize) -> [u64; 8] {
+		let mut delays = [0; 8];
+		for delay in delays.iter_mut().take(count) {
+			*delay = backoff.next_delay().as_ticks();
+		}
+		delays
+	}
+
+	#[test]
+	fn doubles_up_to_max() {
+		let mut backoff =
+			Backoff::new(Duration::from_ticks(100), Duration::from_ticks(1000));
+		assert_eq!(
+			delays(&mut backoff, 6),
+			[100, 200, 400, 800, 1000, 1000, 0, 0]
+		);
+	}
+
+	#[test]
+	fn multiplier() {
+		let mut backoff = Backoff::new(Duration::from_ticks(10), Duration::from_ticks(500))
+			.with_multiplier(3);
+		assert_eq!(delays(&mut backoff, 5), [10, 30, 90, 270, 500, 0, 0, 0]);
+
+		let mut constant =
+			Backoff::new(Duration::from_ticks(10), Duration::from_ticks(500))
+				.with_multiplier(0);
+		assert_eq!(delays(&mut constant, 3), [10, 10, 10, 0, 0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn reset_restarts_sequence() {
+		let mut backoff =
+			Backoff::new(Duration::from_ticks(100), Duration::from_ticks(1000));
+		delays(&mut backoff, 3);
+		backoff.reset();
+		assert_eq!(backoff.next_delay(), Duration::from_ticks(100));
+	}
+
+	#[test]
+	fn jitter_stays_in_range() {
+		let mut backoff =
+			Backoff::new(Duration::from_ticks(1000), Duration::from_ticks(1000))
+				.with_jitter(20, 0x1234_5678);
+		for _ in 0..1000 {
+			let delay = backoff.next_delay();
+			assert!(delay >= Duration::from_ticks(800));
+			assert!(delay <= Duration::from_ticks(1000));
+		}
+	}
+
+	#[test]
+	fn full_jitter_never_zero() {
+		let mut backoff =
+			Backoff::new(Duration::from_ticks(1000), Duration::from_ticks(4000))
+				.with_jitter(100, 42);
+		for _ in 0..1000 {
+			let delay = backoff.next_delay();
+			assert!(delay >= Duration::from_ticks(500));
+			assert!(delay <= Duration::from_ticks(4000));
+		}
+	}
+}
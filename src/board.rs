@@ -0,0 +1,68 @@
+//! Board pin assignments.
+//!
+//! The board is selected at compile time with a cargo feature:
+//! - `board-nrf9151-dk` (default): nRF9151-DK
+//! - `board-custom`: custom hardware; edit the `custom` pins below
+//!
+//! `board_pins!` moves the pins of the selected board out of the
+//! peripherals returned by `embassy_nrf::init`, so `main` does not name
+//! any pin directly. Each arm is the only place its pins are listed; since
+//! a pin can only be moved out once, assigning one pin twice (e.g. the
+//! trace pin to a console pin) does not compile.
+
+#![allow(dead_code)]
+
+use embassy_nrf::gpio::AnyPin;
+use embassy_nrf::Peri;
+
+use crate::modem::TracePin;
+
+#[cfg(all(feature = "board-nrf9151-dk", feature = "board-custom"))]
+compile_error!("Only one board feature can be enabled");
+
+#[cfg(not(any(feature = "board-nrf9151-dk", feature = "board-custom")))]
+compile_error!("No board feature enabled, e.g. `board-nrf9151-dk`");
+
+/// Pins used by the application.
+pub struct BoardPins {
+	/// Console UART RX
+	pub console_rx: Peri<'static, AnyPin>,
+	/// Console UART TX
+	pub console_tx: Peri<'static, AnyPin>,
+	/// Modem trace UART TX
+	pub trace: TracePin,
+	/// Status LED
+	pub led: Peri<'static, AnyPin>,
+}
+
+/// Take the nRF9151-DK pins from the peripherals.
+///
+/// Console on P0.26/P0.27 (VCOM0), traces on P0.29 (VCOM1), LED1 on P0.00.
+#[cfg(feature = "board-nrf9151-dk")]
+#[macro_export]
+macro_rules! board_pins {
+	($p:ident) => {
+		$crate::board::BoardPins {
+			console_rx: $p.P0_26.into(),
+			console_tx: $p.P0_27.into(),
+			trace: $crate::modem::TracePin::nrf9151_dk($p.P0_29),
+			led: $p.P0_00.into(),
+		}
+	};
+}
+
+/// Take the custom board pins from the peripherals.
+///
+/// Edit these to match your hardware.
+#[cfg(feature = "board-custom")]
+#[macro_export]
+macro_rules! board_pins {
+	($p:ident) => {
+		$crate::board::BoardPins {
+			console_rx: $p.P0_26.into(),
+			console_tx: $p.P0_27.into(),
+			trace: $crate::modem::TracePin::custom($p.P0_29),
+			led: $p.P0_00.into(),
+		}
+	};
+}
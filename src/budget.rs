@@ -0,0 +1,174 @@
+//! Monthly data budget.
+//!
+//! Low-data IoT SIMs come with a monthly allowance. Once a `DataBudget` is
+//! installed with `set_budget()`, the TCP helpers in `network` count the
+//! bytes they send and receive against it, and `network::tcp_connect()`
+//! refuses new connections once the cap is reached. The count starts over
+//! on the configured reset day of each month.
+//!
+//! The device has no calendar of its own: call `sync_date()` (network time
+//! from `AT+CCLK?`) after attaching, and again from time to time, so a new
+//! billing period is noticed. Usage is kept in RAM only and starts from
+//! zero after a reboot; compare with `modem::get_conn_stats()` if that
+//! matters. UDP traffic and protocol overhead are not counted, so leave
+//! some headroom below the real allowance.
+//!
+//! ## Error Handling
+//! `check()` returns `Error::Config` when the budget is used up.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+use crate::modem;
+
+use core::cell::RefCell;
+
+use embassy_net_nrf91::Control;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+
+/// Calendar date, as reported by the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+	/// Year within the century (`24` for 2024)
+	pub year: u8,
+	/// Month, 1 to 12
+	pub month: u8,
+	/// Day of the month, 1 to 31
+	pub day: u8,
+}
+
+/// Data allowance per billing period.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataBudget {
+	monthly_cap: u64,
+	reset_day: u8,
+	used: u64,
+	/// Year and month in which the current billing period started
+	period: Option<(u8, u8)>,
+}
+
+impl DataBudget {
+	/// Create a budget of `monthly_cap` bytes per period, starting over on
+	/// `reset_day` of each month (clamped to 1..=28 so every month has it).
+	pub const fn new(monthly_cap: u64, reset_day: u8) -> Self {
+		let reset_day = if reset_day < 1 {
+			1
+		} else if reset_day > 28 {
+			28
+		} else {
+			reset_day
+		};
+		Self {
+			monthly_cap,
+			reset_day,
+			used: 0,
+			period: None,
+		}
+	}
+
+	/// Count `bytes` as used.
+	pub fn record(&mut self, bytes: usize) {
+		self.used = self.used.saturating_add(bytes as u64);
+	}
+
+	/// Bytes used in the current period.
+	pub fn used_bytes(&self) -> u64 {
+		self.used
+	}
+
+	/// Bytes left in the current period.
+	pub fn remaining_bytes(&self) -> u64 {
+		self.monthly_cap.saturating_sub(self.used)
+	}
+
+	/// Start a new period if `today` is past the reset day of a later month.
+	///
+	/// The first date seen only sets the period, keeping the usage so far.
+	pub fn update_date(&mut self, today: Date) {
+		let period = if today.day >= self.reset_day {
+			(today.year, today.month)
+		} else if today.month > 1 {
+			(today.year, today.month - 1)
+		} else {
+			(today.year.wrapping_sub(1), 12)
+		};
+
+		match self.period {
+			Some(current) if current != period => self.used = 0,
+			_ => {}
+		}
+		self.period = Some(period);
+	}
+}
+
+/// Installed budget, if any.
+static BUDGET: Mutex<CriticalSectionRawMutex, RefCell<Option<DataBudget>>> =
+	Mutex::new(RefCell::new(None));
+
+/// Install a budget, or remove it with `None`.
+pub fn set_budget(budget: Option<DataBudget>) {
+	BUDGET.lock(|stored| *stored.borrow_mut() = budget);
+}
+
+/// Get a copy of the installed budget.
+pub fn budget() -> Option<DataBudget> {
+	BUDGET.lock(|stored| *stored.borrow())
+}
+
+/// Count `bytes` against the installed budget, if any.
+pub fn record(bytes: usize) {
+	BUDGET.lock(|stored| {
+		if let Some(budget) = stored.borrow_mut().as_mut() {
+			budget.record(bytes);
+		}
+	});
+}
+
+/// Bytes left in the current period, or `None` without a budget.
+pub fn remaining_bytes() -> Option<u64> {
+	budget().map(|budget| budget.remaining_bytes())
+}
+
+/// Check that the budget allows more traffic.
+///
+/// # Errors
+/// Returns `Error::Config` if a budget is installed and used up.
+pub fn check() -> Result<()> {
+	match remaining_bytes() {
+		Some(0) => Err(Error::Config),
+		_ => Ok(()),
+	}
+}
+
+/// Parse the date from a `+CCLK` response, e.g. `+CCLK: "24/10/15,12:34:56+08"`.
+pub fn parse_cclk_date(response: &str) -> Option<Date> {
+	let value = modem::extract_value(response, "+CCLK:")?;
+	let date = value.split(',').next()?;
+	let mut parts = date.split('/').map(|part| part.parse::<u8>());
+	let date = Date {
+		year: parts.next()?.ok()?,
+		month: parts.next()?.ok()?,
+		day: parts.next()?.ok()?,
+	};
+	((1..=12).contains(&date.month) && (1..=31).contains(&date.day)).then_some(date)
+}
+
+/// Update the installed budget with the network date (`AT+CCLK?`).
+///
+/// # Errors
+/// Returns `Error::InvalidResponse` if the modem has no valid network time
+/// yet.
+pub async fn sync_date(control: &Control<'_>) -> Result<()> {
+	let mut resp_buf = [0u8; 64];
+	let len = modem::at_command(control, "AT+CCLK?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	let today = parse_cclk_date(resp).ok_or(Error::InvalidResponse)?;
+
+	BUDGET.lock(|stored| {
+		if let Some(budget) = stored.borrow_mut().as_mut() {
+			budget.update_date(today);
+		}
+	});
+	Ok(())
+}
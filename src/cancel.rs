@@ -0,0 +1,108 @@
+//! Cancellation of long-running operations.
+//!
+//! A `CancelToken` is shared between the task running a long operation and
+//! the task that may want to abort it (e.g. before going to sleep). The
+//! operation is wrapped with `CancelToken::run()`, which drops it as soon
+//! as the token is cancelled.
+//!
+//! `with_timeout_cancellable()` combines a timeout with an optional token,
+//! for the `_cancellable` variants of the `wait_*` functions.
+//!
+//! ## Error Handling
+//! A cancelled operation returns `Error::Cancelled`.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration};
+
+/// Token used to abort long-running operations.
+///
+/// Typically a `static`, since both sides need a shared reference.
+pub struct CancelToken {
+	cancelled: AtomicBool,
+	signal: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl CancelToken {
+	/// Create a token that is not cancelled.
+	pub const fn new() -> Self {
+		Self {
+			cancelled: AtomicBool::new(false),
+			signal: Signal::new(),
+		}
+	}
+
+	/// Cancel the operations running with this token.
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Release);
+		self.signal.signal(());
+	}
+
+	/// Check whether the token has been cancelled.
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Acquire)
+	}
+
+	/// Re-arm the token for the next operation.
+	pub fn reset(&self) {
+		self.cancelled.store(false, Ordering::Release);
+		self.signal.reset();
+	}
+
+	/// Wait until the token is cancelled.
+	pub async fn cancelled(&self) {
+		while !self.is_cancelled() {
+			self.signal.wait().await;
+		}
+	}
+
+	/// Run `operation` until it completes or the token is cancelled.
+	///
+	/// # Errors
+	/// Returns `Error::Cancelled` if the token is (or becomes) cancelled;
+	/// the operation is dropped at its current await point.
+	pub async fn run<F: Future>(&self, operation: F) -> Result<F::Output> {
+		if self.is_cancelled() {
+			return Err(Error::Cancelled);
+		}
+		match select(operation, self.cancelled()).await {
+			Either::First(output) => Ok(output),
+			Either::Second(()) => Err(Error::Cancelled),
+		}
+	}
+}
+
+impl Default for CancelToken {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Run `operation` with a timeout and an optional cancel token.
+///
+/// # Errors
+/// Returns `Error::Timeout` if `timeout` expires first, or
+/// `Error::Cancelled` if `cancel` is cancelled first.
+pub async fn with_timeout_cancellable<F: Future>(
+	timeout: Duration,
+	cancel: Option<&CancelToken>,
+	operation: F,
+) -> Result<F::Output> {
+	let bounded = async {
+		with_timeout(timeout, operation)
+			.await
+			.map_err(|_| Error::Timeout)
+	};
+	match cancel {
+		Some(token) => token.run(bounded).await?,
+		None => bounded.await,
+	}
+}
@@ -0,0 +1,209 @@
+//! Device configuration stored in flash.
+//!
+//! Deployment-specific settings (APN, SMTP server, credentials, report
+//! interval) live in a dedicated flash page so the same firmware can serve
+//! multiple deployments. A blank page yields `Config::default()`.
+//!
+//! ## Layout
+//! The record is stored at `CONFIG_ADDR` (last flash page, reserved in
+//! memory.x) as a fixed-size little-endian structure with a magic number,
+//! version and checksum. Records from older layout versions are still
+//! read, with defaults for fields added since.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+
+use embassy_time::Duration;
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use heapless::String;
+
+/// Flash address of the configuration page.
+pub const CONFIG_ADDR: u32 = 0x000F_F000;
+
+/// Size of the configuration flash page.
+const PAGE_SIZE: u32 = 4096;
+
+/// Marks a written configuration record ("MPCF").
+const MAGIC: u32 = 0x4D50_4346;
+
+/// Record layout version.
+const VERSION: u8 = 2;
+
+/// Maximum APN length.
+pub const APN_MAX: usize = 64;
+
+/// Maximum SMTP server host name length.
+pub const HOST_MAX: usize = 64;
+
+// Record field offsets
+const OFF_MAGIC: usize = 0;
+const OFF_VERSION: usize = 4;
+const OFF_APN: usize = 5; // length byte followed by APN_MAX bytes
+const OFF_SMTP_SERVER: usize = OFF_APN + 1 + APN_MAX;
+const OFF_SMTP_PORT: usize = OFF_SMTP_SERVER + 1 + HOST_MAX;
+const OFF_CREDENTIALS_TAG: usize = OFF_SMTP_PORT + 2;
+const OFF_REPORT_INTERVAL: usize = OFF_CREDENTIALS_TAG + 4;
+const OFF_MIN_RSRP: usize = OFF_REPORT_INTERVAL + 4; // since version 2
+const OFF_CHECKSUM: usize = OFF_MIN_RSRP + 2;
+
+/// Checksum offset in version 1 records.
+const OFF_CHECKSUM_V1: usize = OFF_REPORT_INTERVAL + 4;
+
+/// Record size, padded to the 4-byte flash write granularity.
+const RECORD_SIZE: usize = (OFF_CHECKSUM + 4 + 3) & !3;
+
+/// Device configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+	/// APN to use. Empty uses the APN provided by the SIM.
+	pub apn: String<APN_MAX>,
+	/// SMTP server host name
+	pub smtp_server: String<HOST_MAX>,
+	/// SMTP server port
+	pub smtp_port: u16,
+	/// Security tag of the SMTP credentials stored in the modem
+	pub credentials_tag: u32,
+	/// Interval between status reports
+	pub report_interval: Duration,
+	/// Weakest signal (RSRP, dBm) at which transmissions are attempted
+	pub min_rsrp_dbm: i16,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			apn: String::new(),
+			smtp_server: String::new(),
+			smtp_port: 587,
+			credentials_tag: 0,
+			report_interval: Duration::from_secs(3600),
+			min_rsrp_dbm: -115,
+		}
+	}
+}
+
+impl Config {
+	/// Serialize into a flash record.
+	fn to_record(&self) -> [u8; RECORD_SIZE] {
+		let mut record = [0u8; RECORD_SIZE];
+		record[OFF_MAGIC..OFF_MAGIC + 4].copy_from_slice(&MAGIC.to_le_bytes());
+		record[OFF_VERSION] = VERSION;
+		write_str(&mut record[OFF_APN..OFF_SMTP_SERVER], &self.apn);
+		write_str(
+			&mut record[OFF_SMTP_SERVER..OFF_SMTP_PORT],
+			&self.smtp_server,
+		);
+		record[OFF_SMTP_PORT..OFF_SMTP_PORT + 2]
+			.copy_from_slice(&self.smtp_port.to_le_bytes());
+		record[OFF_CREDENTIALS_TAG..OFF_CREDENTIALS_TAG + 4]
+			.copy_from_slice(&self.credentials_tag.to_le_bytes());
+		let interval = self.report_interval.as_secs().min(u32::MAX as u64) as u32;
+		record[OFF_REPORT_INTERVAL..OFF_REPORT_INTERVAL + 4]
+			.copy_from_slice(&interval.to_le_bytes());
+		record[OFF_MIN_RSRP..OFF_MIN_RSRP + 2]
+			.copy_from_slice(&self.min_rsrp_dbm.to_le_bytes());
+		let checksum = checksum(&record[..OFF_CHECKSUM]);
+		record[OFF_CHECKSUM..OFF_CHECKSUM + 4].copy_from_slice(&checksum.to_le_bytes());
+		record
+	}
+
+	/// Deserialize from a flash record.
+	///
+	/// Returns `Ok(None)` for a blank (erased) record.
+	fn from_record(record: &[u8; RECORD_SIZE]) -> Result<Option<Self>> {
+		let magic = read_u32(record, OFF_MAGIC);
+		if magic == u32::MAX {
+			return Ok(None);
+		}
+		let version = record[OFF_VERSION];
+		let off_checksum = match version {
+			1 => OFF_CHECKSUM_V1,
+			VERSION => OFF_CHECKSUM,
+			_ => return Err(Error::Config),
+		};
+		if magic != MAGIC
+			|| read_u32(record, off_checksum) != checksum(&record[..off_checksum])
+		{
+			return Err(Error::Config);
+		}
+
+		Ok(Some(Self {
+			apn: read_str(&record[OFF_APN..OFF_SMTP_SERVER])?,
+			smtp_server: read_str(&record[OFF_SMTP_SERVER..OFF_SMTP_PORT])?,
+			smtp_port: u16::from_le_bytes([
+				record[OFF_SMTP_PORT],
+				record[OFF_SMTP_PORT + 1],
+			]),
+			credentials_tag: read_u32(record, OFF_CREDENTIALS_TAG),
+			report_interval: Duration::from_secs(
+				read_u32(record, OFF_REPORT_INTERVAL).into(),
+			),
+			min_rsrp_dbm: if version >= 2 {
+				i16::from_le_bytes([record[OFF_MIN_RSRP], record[OFF_MIN_RSRP + 1]])
+			} else {
+				Self::default().min_rsrp_dbm
+			},
+		}))
+	}
+}
+
+/// Load the configuration from flash.
+///
+/// # Returns
+/// The stored configuration, or `Config::default()` if the page is blank.
+///
+/// # Errors
+/// Returns `Error::Config` if flash cannot be read or the stored record is
+/// corrupt or from an incompatible version.
+pub fn load_config<F: ReadNorFlash>(flash: &mut F) -> Result<Config> {
+	let mut record = [0u8; RECORD_SIZE];
+	flash.read(CONFIG_ADDR, &mut record)
+		.map_err(|_| Error::Config)?;
+	Ok(Config::from_record(&record)?.unwrap_or_default())
+}
+
+/// Save the configuration to flash.
+///
+/// Erases the configuration page and writes the new record.
+///
+/// # Errors
+/// Returns `Error::Config` if erasing or writing flash fails.
+pub fn save_config<F: NorFlash>(flash: &mut F, config: &Config) -> Result<()> {
+	flash.erase(CONFIG_ADDR, CONFIG_ADDR + PAGE_SIZE)
+		.map_err(|_| Error::Config)?;
+	flash.write(CONFIG_ADDR, &config.to_record())
+		.map_err(|_| Error::Config)
+}
+
+/// Write a length-prefixed string into `field`.
+fn write_str(field: &mut [u8], value: &str) {
+	let len = value.len().min(field.len() - 1);
+	field[0] = len as u8;
+	field[1..1 + len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Read a length-prefixed string from `field`.
+fn read_str<const N: usize>(field: &[u8]) -> Result<String<N>> {
+	let len = field[0] as usize;
+	let bytes = field.get(1..1 + len).ok_or(Error::Config)?;
+	let value = core::str::from_utf8(bytes).map_err(|_| Error::Config)?;
+	String::try_from(value).map_err(|_| Error::Config)
+}
+
+/// Read a little-endian u32 at `offset`.
+fn read_u32(record: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes([
+		record[offset],
+		record[offset + 1],
+		record[offset + 2],
+		record[offset + 3],
+	])
+}
+
+/// Simple checksum to detect torn or corrupt writes.
+fn checksum(bytes: &[u8]) -> u32 {
+	bytes.iter().fold(0x811c_9dc5u32, |hash, &b| {
+		(hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+	})
+}
@@ -0,0 +1,84 @@
+//! Interactive AT command console over the main UART.
+//!
+//! Lets a technician type AT commands on the log UART and see the modem's
+//! response, without reflashing. Each line starting with `AT` is forwarded
+//! to the modem and the exchange is logged with `send_at_logged!`.
+
+#![allow(dead_code)]
+
+use embassy_net_nrf91::Control;
+use embassy_nrf::buffered_uarte::{BufferedUarteRx, BufferedUarteTx};
+use embedded_io_async::Read;
+use heapless::Vec;
+
+use crate::logger::SharedLogger;
+use crate::{log, send_at_logged};
+
+/// Logger type for the main UART, shared between main and the console.
+pub type UartLogger = SharedLogger<BufferedUarteTx<'static>>;
+
+/// Maximum length of a console input line.
+const LINE_MAX: usize = 128;
+
+/// Task reading AT commands from the UART and executing them.
+///
+/// Input lines are terminated by CR or LF. Lines longer than `LINE_MAX`
+/// are discarded with an error message instead of being truncated and sent.
+#[embassy_executor::task]
+pub async fn console_task(
+	mut rx: BufferedUarteRx<'static>,
+	control: &'static Control<'static>,
+	uart: &'static UartLogger,
+) {
+	let mut line: Vec<u8, LINE_MAX> = Vec::new();
+	let mut overflow = false;
+	let mut rx_buf = [0u8; 32];
+
+	loop {
+		let n = match Read::read(&mut rx, &mut rx_buf).await {
+			Ok(n) => n,
+			Err(_) => continue,
+		};
+
+		for &byte in &rx_buf[..n] {
+			match byte {
+				b'\r' | b'\n' => {
+					if overflow {
+						log!(
+							uart,
+							"Console: line too long (max {} chars)",
+							LINE_MAX
+						);
+					} else if !line.is_empty() {
+						run_line(control, uart, &line).await;
+					}
+					line.clear();
+					overflow = false;
+				}
+				_ => {
+					if line.push(byte).is_err() {
+						overflow = true;
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Execute a single console line.
+async fn run_line(control: &Control<'_>, uart: &UartLogger, line: &[u8]) {
+	let Ok(cmd) = core::str::from_utf8(line) else {
+		log!(uart, "Console: invalid input");
+		return;
+	};
+
+	let cmd = cmd.trim();
+	if !cmd.get(..2)
+		.is_some_and(|prefix| prefix.eq_ignore_ascii_case("AT"))
+	{
+		log!(uart, "Console: only AT commands are accepted");
+		return;
+	}
+
+	send_at_logged!(control, uart, cmd);
+}
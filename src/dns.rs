@@ -0,0 +1,613 @@
+//! Standalone DNS client over UDP.
+//!
+//! `network::resolve()` uses the embassy-net resolver, which relies on the
+//! DNS servers in the stack configuration and has fixed timeouts. A
+//! `DnsClient` sends its own queries to a given resolver instead, with a
+//! per-attempt timeout and attempt count chosen by the caller. Long
+//! timeouts with few retries suit NB-IoT, where a round trip can take
+//! seconds.
+//!
+//! Queries ask for recursion. A and AAAA answers are returned, following
+//! CNAME records within the response. If the resolver returns only the
+//! alias, the target is queried in turn, up to `CNAME_MAX` times.
+//!
+//! ## Error Handling
+//! Functions return `Result<T, Error>`: `Error::Config` for host names that
+//! cannot be encoded, `Error::Timeout` if no answer arrives within the
+//! attempts, and `Error::InvalidResponse` for error replies (including
+//! unknown names), malformed replies and answers without addresses.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result, SocketError};
+
+use core::ops::Range;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address, Stack};
+use embassy_time::{with_timeout, Duration, Instant};
+use heapless::{String, Vec};
+
+use crate::network;
+
+/// DNS server port.
+pub const DNS_PORT: u16 = 53;
+
+/// Largest DNS message over UDP (RFC 1035).
+pub const MESSAGE_MAX: usize = 512;
+
+/// Longest host name, in dotted form.
+pub const NAME_MAX: usize = 255;
+
+/// Longest label of a host name.
+const LABEL_MAX: usize = 63;
+
+/// Number of addresses returned per lookup.
+pub const ADDRESSES_MAX: usize = 4;
+
+/// Number of CNAME records followed per lookup.
+pub const CNAME_MAX: usize = 8;
+
+/// Default time to wait for an answer, per attempt.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of attempts.
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+
+/// Size of the DNS message header.
+const HEADER_LEN: usize = 12;
+
+/// Header flags: standard query, recursion desired.
+const FLAGS_QUERY: u16 = 0x0100;
+
+/// Header flag set in responses.
+const FLAG_RESPONSE: u16 = 0x8000;
+
+/// Record type of an alias.
+const TYPE_CNAME: u16 = 5;
+
+/// Internet class.
+const CLASS_IN: u16 = 1;
+
+/// Upper bound on labels and compression pointers in one name, which
+/// also stops pointer loops.
+const NAME_PARTS_MAX: usize = 128;
+
+/// Host name in dotted, lower-case form.
+pub type Name = String<NAME_MAX>;
+
+/// Record type to query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordType {
+	/// IPv4 address
+	A = 1,
+	/// IPv6 address
+	Aaaa = 28,
+}
+
+impl RecordType {
+	/// Length of the record data.
+	fn data_len(self) -> usize {
+		match self {
+			Self::A => 4,
+			Self::Aaaa => 16,
+		}
+	}
+}
+
+/// Outcome of parsing a response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Answer {
+	/// Addresses of the queried name, after following CNAME records
+	Addresses(Vec<IpAddress, ADDRESSES_MAX>),
+	/// The name is an alias whose addresses were not included
+	Alias(Name),
+}
+
+/// ID of the next query.
+static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+
+/// Pick a query ID.
+///
+/// There is no RNG in the application core, so the ID mixes a counter with
+/// the uptime. This only tells replies to our own queries apart; it is no
+/// defence against spoofed replies.
+fn next_id() -> u16 {
+	NEXT_ID.fetch_add(1, Ordering::Relaxed) ^ (Instant::now().as_ticks() as u16)
+}
+
+/// Encode a query for `host` into `out`.
+///
+/// # Returns
+/// Length of the query message.
+///
+/// # Errors
+/// Returns `Error::Config` if `host` is not a valid host name or the
+/// message does not fit in `out`.
+pub fn encode_query(id: u16, host: &str, record: RecordType, out: &mut [u8]) -> Result<usize> {
+	let host = host.strip_suffix('.').unwrap_or(host);
+	if host.is_empty() || host.len() > NAME_MAX {
+		return Err(Error::Config);
+	}
+
+	let mut message: Vec<u8, MESSAGE_MAX> = Vec::new();
+	let _ = message.extend_from_slice(&id.to_be_bytes());
+	let _ = message.extend_from_slice(&FLAGS_QUERY.to_be_bytes());
+	// One question, no answer, authority or additional records
+	let _ = message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+	for label in host.split('.') {
+		if label.is_empty() || label.len() > LABEL_MAX {
+			return Err(Error::Config);
+		}
+		message.push(label.len() as u8).map_err(|_| Error::Config)?;
+		message.extend_from_slice(label.as_bytes())
+			.map_err(|_| Error::Config)?;
+	}
+	message.push(0).map_err(|_| Error::Config)?;
+	message.extend_from_slice(&(record as u16).to_be_bytes())
+		.map_err(|_| Error::Config)?;
+	message.extend_from_slice(&CLASS_IN.to_be_bytes())
+		.map_err(|_| Error::Config)?;
+
+	let len = message.len();
+	out.get_mut(..len)
+		.ok_or(Error::Config)?
+		.copy_from_slice(&message);
+	Ok(len)
+}
+
+/// Parse the response to the query `id` for `host`.
+///
+/// # Errors
+/// Returns `Error::InvalidResponse` if the response does not belong to the
+/// query, reports an error (e.g. the name does not exist), is malformed or
+/// has neither addresses nor an alias for `host`.
+pub fn parse_response(message: &[u8], id: u16, host: &str, record: RecordType) -> Result<Answer> {
+	parse_answers(message, id, host, record).ok_or(Error::InvalidResponse)
+}
+
+fn parse_answers(message: &[u8], id: u16, host: &str, record: RecordType) -> Option<Answer> {
+	let header = message.get(..HEADER_LEN)?;
+	let field = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]);
+	let flags = field(2);
+	// Not our reply, or an error such as NXDOMAIN
+	if field(0) != id || flags & FLAG_RESPONSE == 0 || flags & 0x000f != 0 {
+		return None;
+	}
+	let questions = field(4);
+	let answers = field(6);
+
+	let mut owner = Name::new();
+	let mut pos = HEADER_LEN;
+	for _ in 0..questions {
+		pos = read_name(message, pos, &mut owner)? + 4;
+	}
+	let answers_start = pos;
+
+	let mut current = Name::new();
+	for c in host.strip_suffix('.').unwrap_or(host).chars() {
+		current.push(c.to_ascii_lowercase()).ok()?;
+	}
+	let queried = current.clone();
+
+	// Records may come in any order, so follow the chain one pass per link
+	for _ in 0..=CNAME_MAX {
+		let mut addresses = Vec::new();
+		let mut alias = None;
+
+		let mut pos = answers_start;
+		for _ in 0..answers {
+			let (rtype, data, next) = read_record(message, pos, &mut owner)?;
+			pos = next;
+			if owner != current {
+				continue;
+			}
+			if rtype == record as u16 && data.len() == record.data_len() {
+				let _ = addresses.push(to_address(&message[data]));
+			} else if rtype == TYPE_CNAME {
+				let mut target = Name::new();
+				read_name(message, data.start, &mut target)?;
+				alias = Some(target);
+			}
+		}
+
+		if !addresses.is_empty() {
+			return Some(Answer::Addresses(addresses));
+		}
+		match alias {
+			Some(target) => current = target,
+			None => break,
+		}
+	}
+
+	(current != queried).then_some(Answer::Alias(current))
+}
+
+/// Convert A or AAAA record data to an address.
+fn to_address(data: &[u8]) -> IpAddress {
+	match <[u8; 4]>::try_from(data) {
+		Ok(octets) => IpAddress::Ipv4(Ipv4Address::from(octets)),
+		Err(_) => {
+			let mut octets = [0u8; 16];
+			octets.copy_from_slice(data);
+			IpAddress::Ipv6(Ipv6Address::from(octets))
+		}
+	}
+}
+
+/// Read a resource record starting at `pos`, storing its owner name.
+///
+/// # Returns
+/// `(type, data range, position after the record)`.
+fn read_record(message: &[u8], pos: usize, owner: &mut Name) -> Option<(u16, Range<usize>, usize)> {
+	let pos = read_name(message, pos, owner)?;
+	// <type> <class> <ttl> <rdlength>
+	let fixed = message.get(pos..pos + 10)?;
+	let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+	let len = usize::from(u16::from_be_bytes([fixed[8], fixed[9]]));
+	let data = pos + 10..pos + 10 + len;
+	if data.end > message.len() {
+		return None;
+	}
+	let next = data.end;
+	Some((rtype, data, next))
+}
+
+/// Read a possibly compressed name starting at `pos` into `name`.
+///
+/// # Returns
+/// The position after the name as it appears at `pos`.
+fn read_name(message: &[u8], mut pos: usize, name: &mut Name) -> Option<usize> {
+	name.clear();
+	let mut end = None;
+	for _ in 0..NAME_PARTS_MAX {
+		let len = usize::from(*message.get(pos)?);
+		match len & 0xc0 {
+			0x00 if len == 0 => return Some(end.unwrap_or(pos + 1)),
+			0x00 => {
+				let label = message.get(pos + 1..pos + 1 + len)?;
+				if !name.is_empty() {
+					name.push('.').ok()?;
+				}
+				for &byte in label {
+					name.push(char::from(byte.to_ascii_lowercase())).ok()?;
+				}
+				pos += 1 + len;
+			}
+			0xc0 => {
+				let low = usize::from(*message.get(pos + 1)?);
+				end.get_or_insert(pos + 2);
+				pos = ((len & 0x3f) << 8) | low;
+			}
+			_ => return None,
+		}
+	}
+	None
+}
+
+/// DNS client for one resolver.
+#[derive(Clone, Copy, Debug)]
+pub struct DnsClient {
+	server: IpEndpoint,
+	timeout: Duration,
+	attempts: u32,
+}
+
+impl DnsClient {
+	/// Client for the resolver at `server`, with `DEFAULT_TIMEOUT` and
+	/// `DEFAULT_ATTEMPTS`.
+	pub const fn new(server: IpAddress) -> Self {
+		Self {
+			server: IpEndpoint::new(server, DNS_PORT),
+			timeout: DEFAULT_TIMEOUT,
+			attempts: DEFAULT_ATTEMPTS,
+		}
+	}
+
+	/// Set the time to wait for an answer, per attempt.
+	pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = timeout;
+		self
+	}
+
+	/// Set the number of attempts per query (at least 1).
+	pub const fn with_attempts(mut self, attempts: u32) -> Self {
+		self.attempts = if attempts == 0 { 1 } else { attempts };
+		self
+	}
+
+	/// Look up the `record` addresses of `host`.
+	///
+	/// # Returns
+	/// Up to `ADDRESSES_MAX` addresses.
+	///
+	/// # Errors
+	/// Returns `Error::Config` if `host` is not a valid host name,
+	/// `Error::Socket` if the socket cannot be bound or the query cannot be
+	/// sent, `Error::Timeout` if
+	/// the resolver did not answer, or `Error::InvalidResponse` if the
+	/// answer has no addresses.
+	pub async fn resolve(
+		&self,
+		stack: &Stack<'_>,
+		host: &str,
+		record: RecordType,
+	) -> Result<Vec<IpAddress, ADDRESSES_MAX>> {
+		let mut rx_meta = [PacketMetadata::EMPTY; 1];
+		let mut tx_meta = [PacketMetadata::EMPTY; 1];
+		let mut rx_buffer = [0u8; MESSAGE_MAX];
+		let mut tx_buffer = [0u8; MESSAGE_MAX];
+		let mut socket = UdpSocket::new(
+			*stack,
+			&mut rx_meta,
+			&mut rx_buffer,
+			&mut tx_meta,
+			&mut tx_buffer,
+		);
+		// An ephemeral port needs no release, so the binding is not kept
+		let _ = network::udp_bind(&mut socket, None)?;
+
+		let mut name = Name::try_from(host).map_err(|_| Error::Config)?;
+		for _ in 0..=CNAME_MAX {
+			match self.exchange(&socket, &name, record).await? {
+				Answer::Addresses(addresses) => return Ok(addresses),
+				Answer::Alias(target) => name = target,
+			}
+		}
+		Err(Error::InvalidResponse)
+	}
+
+	/// Send a query for `host` and wait for its answer, retrying on timeout.
+	async fn exchange(
+		&self,
+		socket: &UdpSocket<'_>,
+		host: &str,
+		record: RecordType,
+	) -> Result<Answer> {
+		let id = next_id();
+		let mut query = [0u8; MESSAGE_MAX];
+		let len = encode_query(id, host, record, &mut query)?;
+
+		let mut reply = [0u8; MESSAGE_MAX];
+		for _ in 0..self.attempts {
+			if socket.send_to(&query[..len], self.server).await.is_err() {
+				return Err(Error::Socket(SocketError::NoRoute));
+			}
+
+			let answer = with_timeout(self.timeout, async {
+				loop {
+					// Skip stray packets; replies to earlier attempts carry
+					// the same ID and are accepted
+					let Ok((n, meta)) = socket.recv_from(&mut reply).await
+					else {
+						continue;
+					};
+					if meta.endpoint != self.server {
+						continue;
+					}
+					if let Ok(answer) =
+						parse_response(&reply[..n], id, host, record)
+					{
+						return Ok(answer);
+					}
+					// Our reply, but an error or malformed
+					if reply[..n].starts_with(&id.to_be_bytes()) {
+						return Err(Error::InvalidResponse);
+					}
+				}
+			})
+			.await;
+
+			if let Ok(result) = answer {
+				return result;
+			}
+		}
+		Err(Error::Timeout)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ID: u16 = 0x1234;
+
+	/// Offset of the question name in a message.
+	const QNAME: u8 = HEADER_LEN as u8;
+
+	/// Response to the query for `host`, followed by `count` answer records.
+	fn response(
+		host: &str,
+		record: RecordType,
+		rcode: u8,
+		count: u8,
+		records: &[u8],
+	) -> Vec<u8, MESSAGE_MAX> {
+		let mut query = [0u8; MESSAGE_MAX];
+		let len = encode_query(ID, host, record, &mut query).unwrap();
+		query[2] |= 0x80;
+		query[3] |= 0x80 | rcode;
+		query[7] = count;
+
+		let mut message = Vec::new();
+		message.extend_from_slice(&query[..len]).unwrap();
+		message.extend_from_slice(records).unwrap();
+		message
+	}
+
+	#[test]
+	fn query_example_com() {
+		let mut out = [0u8; MESSAGE_MAX];
+		let len = encode_query(ID, "example.com", RecordType::A, &mut out).unwrap();
+
+		let expected = [
+			0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 7, b'e', b'x', b'a', b'm',
+			b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, 0, 1, 0, 1,
+		];
+		assert_eq!(&out[..len], &expected);
+
+		let mut dotted = [0u8; MESSAGE_MAX];
+		let dotted_len =
+			encode_query(ID, "example.com.", RecordType::A, &mut dotted).unwrap();
+		assert_eq!(&dotted[..dotted_len], &expected);
+	}
+
+	#[test]
+	fn query_invalid_names() {
+		let mut out = [0u8; MESSAGE_MAX];
+		let label = [b'x'; LABEL_MAX + 1];
+		let label = core::str::from_utf8(&label).unwrap();
+
+		assert_eq!(
+			encode_query(ID, "", RecordType::A, &mut out),
+			Err(Error::Config)
+		);
+		assert_eq!(
+			encode_query(ID, ".", RecordType::A, &mut out),
+			Err(Error::Config)
+		);
+		assert_eq!(
+			encode_query(ID, "a..b", RecordType::A, &mut out),
+			Err(Error::Config)
+		);
+		assert_eq!(
+			encode_query(ID, label, RecordType::A, &mut out),
+			Err(Error::Config)
+		);
+		assert!(encode_query(ID, &label[1..], RecordType::A, &mut out).is_ok());
+
+		// Four full labels make 255 bytes; one more label is too long
+		let mut name: String<{ NAME_MAX + 8 }> = String::new();
+		for _ in 0..4 {
+			name.push_str(&label[1..]).unwrap();
+			name.push('.').unwrap();
+		}
+		name.push_str("ab").unwrap();
+		assert_eq!(
+			encode_query(ID, &name, RecordType::A, &mut out),
+			Err(Error::Config)
+		);
+	}
+
+	#[test]
+	fn answer_a() {
+		#[rustfmt::skip]
+		let message = response("example.com", RecordType::A, 0, 1, &[
+			0xc0, QNAME, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34,
+		]);
+
+		let Ok(Answer::Addresses(addresses)) =
+			parse_response(&message, ID, "Example.com.", RecordType::A)
+		else {
+			panic!("no addresses");
+		};
+		assert_eq!(
+			&addresses[..],
+			&[IpAddress::Ipv4(Ipv4Address::new(93, 184, 216, 34))]
+		);
+	}
+
+	#[test]
+	fn answer_aaaa() {
+		#[rustfmt::skip]
+		let message = response("example.com", RecordType::Aaaa, 0, 1, &[
+			0xc0, QNAME, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16,
+			0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+		]);
+
+		let Ok(Answer::Addresses(addresses)) =
+			parse_response(&message, ID, "example.com", RecordType::Aaaa)
+		else {
+			panic!("no addresses");
+		};
+		assert_eq!(
+			&addresses[..],
+			&[IpAddress::Ipv6("2001:db8::1".parse().unwrap())]
+		);
+	}
+
+	#[test]
+	fn cname_chain_out_of_order() {
+		// www -> cdn -> web, with the A record of web first. The question
+		// ends at 33, where the first record starts.
+		#[rustfmt::skip]
+		let message = response("www.example.com", RecordType::A, 0, 3, &[
+			// web.example.com A 10.1.2.3
+			3, b'w', b'e', b'b', 0xc0, 16, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 1, 2, 3,
+			// cdn.example.com CNAME web.example.com, at 53
+			3, b'c', b'd', b'n', 0xc0, 16, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 33,
+			// www.example.com CNAME cdn.example.com
+			0xc0, QNAME, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 53,
+		]);
+
+		let Ok(Answer::Addresses(addresses)) =
+			parse_response(&message, ID, "www.example.com", RecordType::A)
+		else {
+			panic!("no addresses");
+		};
+		assert_eq!(
+			&addresses[..],
+			&[IpAddress::Ipv4(Ipv4Address::new(10, 1, 2, 3))]
+		);
+	}
+
+	#[test]
+	fn alias_only() {
+		#[rustfmt::skip]
+		let message = response("www.example.com", RecordType::A, 0, 1, &[
+			0xc0, QNAME, 0, 5, 0, 1, 0, 0, 0, 60, 0, 6, 3, b'c', b'd', b'n', 0xc0, 16,
+		]);
+
+		assert_eq!(
+			parse_response(&message, ID, "www.example.com", RecordType::A),
+			Ok(Answer::Alias(Name::try_from("cdn.example.com").unwrap()))
+		);
+	}
+
+	#[test]
+	fn pointer_loop() {
+		// The owner name of the record points at itself
+		let message = response("www.example.com", RecordType::A, 0, 1, &[0xc0, 33]);
+
+		assert_eq!(
+			parse_response(&message, ID, "www.example.com", RecordType::A),
+			Err(Error::InvalidResponse)
+		);
+	}
+
+	#[test]
+	fn error_and_foreign_replies() {
+		// NXDOMAIN
+		let message = response("example.com", RecordType::A, 3, 0, &[]);
+		assert_eq!(
+			parse_response(&message, ID, "example.com", RecordType::A),
+			Err(Error::InvalidResponse)
+		);
+
+		#[rustfmt::skip]
+		let message = response("example.com", RecordType::A, 0, 1, &[
+			0xc0, QNAME, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34,
+		]);
+		assert_eq!(
+			parse_response(&message, ID + 1, "example.com", RecordType::A),
+			Err(Error::InvalidResponse)
+		);
+	}
+
+	#[test]
+	fn truncated_messages() {
+		#[rustfmt::skip]
+		let message = response("example.com", RecordType::A, 0, 1, &[
+			0xc0, QNAME, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34,
+		]);
+
+		for len in [0, HEADER_LEN - 1, HEADER_LEN + 5, message.len() - 1] {
+			assert_eq!(
+				parse_response(&message[..len], ID, "example.com", RecordType::A),
+				Err(Error::InvalidResponse)
+			);
+		}
+	}
+}
@@ -20,15 +20,19 @@ pub enum Error {
 	/// Network stack initialization failed
 	NetworkInit,
 	/// TCP/IP socket error
-	Socket,
+	Socket(SocketError),
 	/// Timeout waiting for operation
 	Timeout,
+	/// AT command did not complete in time; carries the command
+	AtTimeout(&'static str),
 	/// Invalid response from modem
 	InvalidResponse,
 	/// Task spawn failed
 	TaskSpawn,
 	/// Configuration error
 	Config,
+	/// Operation aborted through a `CancelToken`
+	Cancelled,
 }
 
 impl fmt::Display for Error {
@@ -39,15 +43,100 @@ impl fmt::Display for Error {
 			Error::Registration => write!(f, "Network registration failed"),
 			Error::PdpActivation => write!(f, "PDP context activation failed"),
 			Error::NetworkInit => write!(f, "Network stack initialization failed"),
-			Error::Socket => write!(f, "Socket error"),
+			Error::Socket(reason) => write!(f, "Socket error: {}", reason),
 			Error::Timeout => write!(f, "Operation timed out"),
+			Error::AtTimeout(cmd) => write!(f, "AT command timed out: {}", cmd),
 			Error::InvalidResponse => write!(f, "Invalid response from modem"),
 			Error::TaskSpawn => write!(f, "Failed to spawn task"),
 			Error::Config => write!(f, "Configuration error"),
+			Error::Cancelled => write!(f, "Operation cancelled"),
 		}
 	}
 }
 
+/// Reason for a TCP/IP socket error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum SocketError {
+	/// Peer refused the connection (RST in reply to SYN)
+	ConnectionRefused,
+	/// Connection reset by the peer after it was established
+	ConnectionReset,
+	/// Connect or I/O timed out
+	Timeout,
+	/// No route to the remote host (stack not configured)
+	NoRoute,
+	/// Socket was not in a state allowing the operation
+	InvalidState,
+	/// Peer closed the connection
+	Closed,
+	/// Requested local port is already bound by another socket
+	AddressInUse,
+	/// No room to track another fixed local port
+	TooManyPorts,
+}
+
+impl fmt::Display for SocketError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SocketError::ConnectionRefused => write!(f, "connection refused"),
+			SocketError::ConnectionReset => write!(f, "connection reset"),
+			SocketError::Timeout => write!(f, "timed out"),
+			SocketError::NoRoute => write!(f, "no route to host"),
+			SocketError::InvalidState => write!(f, "invalid socket state"),
+			SocketError::Closed => write!(f, "connection closed"),
+			SocketError::AddressInUse => write!(f, "address in use"),
+			SocketError::TooManyPorts => write!(f, "too many fixed ports"),
+		}
+	}
+}
+
+impl From<embassy_net::tcp::ConnectError> for Error {
+	fn from(e: embassy_net::tcp::ConnectError) -> Self {
+		use embassy_net::tcp::ConnectError;
+		// A reset while connecting means the peer refused the connection
+		Error::Socket(match e {
+			ConnectError::ConnectionReset => SocketError::ConnectionRefused,
+			ConnectError::TimedOut => SocketError::Timeout,
+			ConnectError::NoRoute => SocketError::NoRoute,
+			ConnectError::InvalidState => SocketError::InvalidState,
+		})
+	}
+}
+
+impl From<embassy_net::tcp::Error> for Error {
+	fn from(e: embassy_net::tcp::Error) -> Self {
+		match e {
+			embassy_net::tcp::Error::ConnectionReset => {
+				Error::Socket(SocketError::ConnectionReset)
+			}
+		}
+	}
+}
+
+impl From<embassy_net::tcp::AcceptError> for Error {
+	fn from(e: embassy_net::tcp::AcceptError) -> Self {
+		use embassy_net::tcp::AcceptError;
+		Error::Socket(match e {
+			AcceptError::ConnectionReset => SocketError::ConnectionReset,
+			AcceptError::InvalidPort | AcceptError::InvalidState => {
+				SocketError::InvalidState
+			}
+		})
+	}
+}
+
+impl From<embassy_net::udp::BindError> for Error {
+	fn from(e: embassy_net::udp::BindError) -> Self {
+		use embassy_net::udp::BindError;
+		Error::Socket(match e {
+			// Port conflicts are detected by `network::udp_bind`
+			BindError::InvalidState => SocketError::InvalidState,
+			BindError::NoRoute => SocketError::NoRoute,
+		})
+	}
+}
+
 /// Result type alias for this application.
 pub type Result<T> = core::result::Result<T, Error>;
 
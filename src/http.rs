@@ -0,0 +1,182 @@
+//! Minimal HTTP/1.1 client for telemetry uploads.
+//!
+//! `http_post()` sends one request per connection over plain TCP (port 80)
+//! with `Connection: close`, and reads the response until the server closes
+//! the connection. There is no TLS; use it for endpoints reached over a
+//! private APN or through a gateway.
+//!
+//! ## Request Bodies
+//! A `Body::Fixed` body is sent with `Content-Length`. A `Body::Chunked`
+//! body is produced piece by piece by a callback and sent with
+//! `Transfer-Encoding: chunked`, so large payloads need not fit in RAM.
+//!
+//! ## Responses
+//! The status code and the body are returned. A chunked response body is
+//! returned as received, chunk framing included; the telemetry backends
+//! this is meant for answer with short fixed-length bodies.
+//!
+//! ## Error Handling
+//! Functions return `Result<T, Error>`: `Error::InvalidResponse` for DNS
+//! failures and malformed responses, `Error::Socket` for connection
+//! failures, and `Error::Config` if the request head does not fit.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+use crate::network::{self, LineReader};
+
+use core::fmt::Write as _;
+
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{IpEndpoint, Stack};
+use embassy_time::Duration;
+use heapless::String;
+
+/// Server port for plain HTTP.
+const HTTP_PORT: u16 = 80;
+
+/// Inactivity timeout for the connection.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Size of the socket RX and TX buffers.
+const SOCKET_BUF_SIZE: usize = 1024;
+
+/// Maximum size of the request line and headers.
+const HEAD_MAX: usize = 512;
+
+/// Longest response status or header line accepted.
+const LINE_MAX: usize = 512;
+
+/// Size of the chunks requested from a `Body::Chunked` callback.
+pub const CHUNK_SIZE: usize = 256;
+
+/// Request body.
+pub enum Body<'a> {
+	/// Body known up front, sent with `Content-Length`.
+	Fixed(&'a [u8]),
+	/// Body produced by a callback, sent with chunked transfer encoding.
+	///
+	/// The callback fills the buffer it is given (`CHUNK_SIZE` bytes) and
+	/// returns the number of bytes written; returning `0` ends the body.
+	Chunked(&'a mut dyn FnMut(&mut [u8]) -> usize),
+}
+
+/// Send an HTTP POST request and read the response.
+///
+/// # Arguments
+/// * `stack` - Network stack, with DNS servers configured
+/// * `host` - Server host name, also sent as the `Host` header
+/// * `path` - Request path, e.g. `/api/telemetry`
+/// * `content_type` - `Content-Type` of the body, e.g. `application/json`
+/// * `body` - Request body, see `Body`
+/// * `out` - Buffer for the response body; bytes beyond it are discarded
+///
+/// # Returns
+/// `(status_code, body_len)`, with `body_len` the number of bytes stored
+/// in `out`.
+///
+/// # Errors
+/// Returns `Error::InvalidResponse` if DNS resolution fails or the status
+/// line is malformed, `Error::Socket` if the connection fails, and
+/// `Error::Config` if the request head or a response header line is too
+/// long.
+pub async fn http_post(
+	stack: &Stack<'_>,
+	host: &str,
+	path: &str,
+	content_type: &str,
+	body: Body<'_>,
+	out: &mut [u8],
+) -> Result<(u16, usize)> {
+	let mut head: String<HEAD_MAX> = String::new();
+	write!(
+		head,
+		"POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nConnection: close\r\n",
+		path, host, content_type
+	)
+	.map_err(|_| Error::Config)?;
+	match &body {
+		Body::Fixed(data) => write!(head, "Content-Length: {}\r\n\r\n", data.len()),
+		Body::Chunked(_) => write!(head, "Transfer-Encoding: chunked\r\n\r\n"),
+	}
+	.map_err(|_| Error::Config)?;
+
+	let endpoint = IpEndpoint::new(network::resolve(stack, host).await?, HTTP_PORT);
+
+	let mut rx_buffer = [0u8; SOCKET_BUF_SIZE];
+	let mut tx_buffer = [0u8; SOCKET_BUF_SIZE];
+	let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+	socket.set_timeout(Some(HTTP_TIMEOUT));
+	network::tcp_connect(&mut socket, endpoint).await?;
+
+	network::tcp_write_all(&mut socket, head.as_bytes()).await?;
+	match body {
+		Body::Fixed(data) => network::tcp_write_all(&mut socket, data).await?,
+		Body::Chunked(next_chunk) => write_chunked(&mut socket, next_chunk).await?,
+	}
+
+	let response = read_response(&mut socket, out).await;
+	socket.close();
+	let _ = socket.flush().await;
+	response
+}
+
+/// Write a chunked body, ending with the zero-length chunk.
+async fn write_chunked(
+	socket: &mut TcpSocket<'_>,
+	next_chunk: &mut dyn FnMut(&mut [u8]) -> usize,
+) -> Result<()> {
+	let mut chunk = [0u8; CHUNK_SIZE];
+	loop {
+		let len = next_chunk(&mut chunk).min(CHUNK_SIZE);
+
+		let mut size: String<8> = String::new();
+		let _ = write!(size, "{:x}\r\n", len);
+		network::tcp_write_all(socket, size.as_bytes()).await?;
+		if len == 0 {
+			return network::tcp_write_all(socket, b"\r\n").await;
+		}
+		network::tcp_write_all(socket, &chunk[..len]).await?;
+		network::tcp_write_all(socket, b"\r\n").await?;
+	}
+}
+
+/// Read the status line, skip the headers and read the body into `out`
+/// until the server closes the connection.
+async fn read_response(socket: &mut TcpSocket<'_>, out: &mut [u8]) -> Result<(u16, usize)> {
+	let mut line_buf = [0u8; LINE_MAX];
+	let mut reader = LineReader::new(socket, &mut line_buf);
+
+	let status = parse_status_line(reader.next_line().await?).ok_or(Error::InvalidResponse)?;
+	while !reader.next_line().await?.is_empty() {}
+
+	// Body bytes already read along with the headers
+	let buffered = reader.buffered();
+	let mut len = buffered.len().min(out.len());
+	out[..len].copy_from_slice(&buffered[..len]);
+
+	let mut discard = [0u8; 64];
+	loop {
+		let n = if len < out.len() {
+			network::tcp_read(socket, &mut out[len..]).await?
+		} else {
+			network::tcp_read(socket, &mut discard).await?
+		};
+		if n == 0 {
+			return Ok((status, len));
+		}
+		if len < out.len() {
+			len += n;
+		}
+	}
+}
+
+/// Parse the status code from a status line, e.g. `HTTP/1.1 200 OK`.
+fn parse_status_line(line: &[u8]) -> Option<u16> {
+	let line = core::str::from_utf8(line).ok()?;
+	let mut parts = line.split(' ');
+	if !parts.next()?.starts_with("HTTP/") {
+		return None;
+	}
+	parts.next()?.parse().ok()
+}
@@ -1,8 +1,105 @@
 //! UART logging utilities for embedded applications.
 //!
-//! Provides macros and utilities for logging over UART.
+//! Provides a `Logger` that owns a persistent line buffer and a UART
+//! writer, plus macros that log through it. Reusing the buffer keeps
+//! per-call stack usage and overhead low during chatty phases such as
+//! network registration.
+//!
+//! With the `net-log` feature, each line is also mirrored to a remote
+//! collector, see `net_log`.
+
+#![allow(dead_code)]
+
+use core::fmt::{self, Write as _};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embedded_io_async::Write;
+use heapless::String;
+
+/// Size of the AT response buffers, i.e. of the `at_queue` pool buffers
+/// that `send_at_logged!` and the AT helpers read responses from.
+///
+/// Large enough for long responses such as `AT+COPS=?` scans or
+/// `AT%XMONITOR`; longer responses are cut off by the modem driver.
+pub const AT_RESP_BUF_SIZE: usize = 1024;
 
-/// Log a formatted message over UART.
+/// Size of the line buffer used to format log messages.
+///
+/// Fits a full `send_at_logged!` response plus its prefix, so responses are
+/// not truncated when logged.
+pub const LOG_BUF_SIZE: usize = AT_RESP_BUF_SIZE + 32;
+
+/// Whether debug-level messages (`log_debug!`) are logged.
+///
+/// Enabled with the `log-debug` feature.
+pub const DEBUG_ENABLED: bool = cfg!(feature = "log-debug");
+
+/// Line terminator appended to every log message.
+const LINE_END: &str = "\r\n";
+
+/// Logger writing formatted lines to a UART (typically a DMA-backed
+/// `BufferedUarteTx`).
+pub struct Logger<W> {
+	writer: W,
+	buf: String<LOG_BUF_SIZE>,
+}
+
+impl<W: Write> Logger<W> {
+	/// Create a logger writing to `writer`.
+	pub fn new(writer: W) -> Self {
+		Self {
+			writer,
+			buf: String::new(),
+		}
+	}
+
+	/// Format and write a single log line.
+	///
+	/// Messages longer than the buffer are truncated; the line ending is
+	/// always written.
+	pub async fn log_fmt(&mut self, args: fmt::Arguments<'_>) {
+		self.buf.clear();
+		let _ = self.buf.write_fmt(args);
+
+		#[cfg(feature = "net-log")]
+		crate::net_log::push(&self.buf);
+
+		if self.buf.push_str(LINE_END).is_err() {
+			// Make room for the line ending on a char boundary
+			while self.buf.len() > LOG_BUF_SIZE - LINE_END.len() {
+				self.buf.pop();
+			}
+			let _ = self.buf.push_str(LINE_END);
+		}
+
+		let _ = self.writer.write_all(self.buf.as_bytes()).await;
+	}
+}
+
+/// `Logger` that can be shared between tasks.
+///
+/// Each log line is written while holding a lock, so lines from
+/// different tasks never interleave.
+pub struct SharedLogger<W> {
+	inner: Mutex<CriticalSectionRawMutex, Logger<W>>,
+}
+
+impl<W: Write> SharedLogger<W> {
+	/// Create a shared logger writing to `writer`.
+	pub fn new(writer: W) -> Self {
+		Self {
+			inner: Mutex::new(Logger::new(writer)),
+		}
+	}
+
+	/// Format and write a single log line.
+	pub async fn log_fmt(&self, args: fmt::Arguments<'_>) {
+		self.inner.lock().await.log_fmt(args).await;
+	}
+}
+
+/// Log a formatted message through a `Logger` or `SharedLogger`.
 ///
 /// # Example
 /// ```ignore
@@ -12,11 +109,22 @@
 #[macro_export]
 macro_rules! log {
 	($uart:expr, $($arg:tt)*) => {{
-		use core::fmt::Write as _;
-		let mut buf: heapless::String<256> = heapless::String::new();
-		let _ = core::write!(&mut buf, $($arg)*);
-		let _ = buf.push_str("\r\n");
-		let _ = $uart.write(buf.as_bytes()).await;
+		$uart.log_fmt(core::format_args!($($arg)*)).await;
+	}};
+}
+
+/// Log a debug-level message; compiled out unless `log-debug` is enabled.
+///
+/// # Example
+/// ```ignore
+/// log_debug!(uart, "CEREG raw: {}", resp);
+/// ```
+#[macro_export]
+macro_rules! log_debug {
+	($uart:expr, $($arg:tt)*) => {{
+		if $crate::logger::DEBUG_ENABLED {
+			$crate::log!($uart, $($arg)*);
+		}
 	}};
 }
 
@@ -29,24 +137,26 @@ macro_rules! log {
 #[macro_export]
 macro_rules! log_at {
 	($uart:expr, $cmd:expr, $resp:expr) => {{
-		use core::fmt::Write as _;
-		let mut buf: heapless::String<256> = heapless::String::new();
-		let _ = core::write!(&mut buf, ">> {}", $cmd);
-		let _ = buf.push_str("\r\n");
-		let _ = $uart.write(buf.as_bytes()).await;
-
-		let mut buf: heapless::String<256> = heapless::String::new();
-		let _ = core::write!(&mut buf, "<< {}", $resp);
-		let _ = buf.push_str("\r\n");
-		let _ = $uart.write(buf.as_bytes()).await;
+		$uart.log_fmt(core::format_args!(">> {}", $cmd)).await;
+		$uart.log_fmt(core::format_args!("<< {}", $resp)).await;
 	}};
 }
 
 /// Send AT command via modem control and log the exchange.
 ///
+/// Each non-empty response line is logged on its own `<<` line. The first
+/// one is prefixed with the time the command took, so stalled commands
+/// stand out. Output format:
+/// ```text
+/// >> AT+CGDCONT?
+/// << [12 ms] +CGDCONT: 0,"IP","internet","10.0.0.1",0,0
+/// << +CGDCONT: 1,"IP","ims","",0,0
+/// << OK
+/// ```
+///
 /// # Arguments
 /// * `control` - Modem control reference
-/// * `uart` - UART interface for logging
+/// * `uart` - `Logger` used for output
 /// * `cmd` - AT command string
 ///
 /// # Example
@@ -56,25 +166,38 @@ macro_rules! log_at {
 #[macro_export]
 macro_rules! send_at_logged {
 	($control:expr, $uart:expr, $cmd:expr) => {{
-		use core::fmt::Write as _;
-
 		// Log command
-		let mut buf: heapless::String<256> = heapless::String::new();
-		let _ = core::write!(&mut buf, ">> {}", $cmd);
-		let _ = buf.push_str("\r\n");
-		let _ = $uart.write(buf.as_bytes()).await;
-
-		// Send command
-		let mut resp_buf = [0u8; 256];
-		let len = $control.at_command($cmd.as_bytes(), &mut resp_buf).await;
-
-		// Log response
-		if len > 0 {
-			if let Ok(resp_str) = core::str::from_utf8(&resp_buf[..len]) {
-				let mut buf: heapless::String<256> = heapless::String::new();
-				let _ = core::write!(&mut buf, "<< {}", resp_str.trim());
-				let _ = buf.push_str("\r\n");
-				let _ = $uart.write(buf.as_bytes()).await;
+		$uart.log_fmt(core::format_args!(">> {}", $cmd)).await;
+
+		// Send command, keeping the response in an AT queue pool buffer
+		let start = embassy_time::Instant::now();
+		let response = $crate::modem::at_response(&$control, $cmd).await;
+		let elapsed_ms = start.elapsed().as_millis();
+
+		// Log each response line, the first with elapsed time
+		let resp_str = match &response {
+			Ok(response) => core::str::from_utf8(response).unwrap_or("<invalid utf-8>"),
+			Err(_) => "<command too long>",
+		};
+		let mut lines = resp_str
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty());
+		match lines.next() {
+			Some(first) => {
+				$uart.log_fmt(core::format_args!(
+					"<< [{} ms] {}",
+					elapsed_ms,
+					first
+				))
+				.await;
+				for line in lines {
+					$uart.log_fmt(core::format_args!("<< {}", line)).await;
+				}
+			}
+			None => {
+				$uart.log_fmt(core::format_args!("<< [{} ms]", elapsed_ms))
+					.await
 			}
 		}
 
@@ -1,32 +1,93 @@
 #![no_std]
 #![no_main]
 
+mod at_builder;
+mod at_queue;
+#[cfg(feature = "at-timing")]
+mod at_stats;
+mod backoff;
+mod board;
+mod budget;
+mod cancel;
+mod config;
+mod console;
+mod dns;
 mod error;
+mod http;
 mod logger;
 mod modem;
+#[cfg(feature = "net-log")]
+mod net_log;
 mod network;
+mod operator;
 mod pdp;
 mod registration;
+mod report;
+mod reset;
+mod retry;
+mod status_led;
+mod telemetry;
 
 use panic_halt as _;
 
 use embassy_executor::Spawner;
 use embassy_net::tcp::TcpSocket;
+use embassy_net::Ipv4Address;
+use embassy_net_nrf91::Control;
+use embassy_nrf::buffered_uarte::{self, BufferedUarte};
 use embassy_nrf::gpio::{Level, Output, OutputDrive};
-use embassy_nrf::uarte::{self, Uarte};
+use embassy_nrf::nvmc::Nvmc;
+use embassy_nrf::uarte;
 use embassy_nrf::{bind_interrupts, peripherals};
-use embassy_time::Timer;
+use embassy_time::{with_deadline, Duration, Timer};
+use static_cell::StaticCell;
 
-use registration::wait_for_status_change;
+use backoff::Backoff;
+use console::UartLogger;
+use error::Error;
+use logger::SharedLogger;
+use registration::{connectivity_stats, wait_for_status_change, CeregMode, SearchWatchdog};
+use retry::RetryPolicy;
+use status_led::LedPattern;
+
+/// Maximum time to get online (network registration and PDP activation).
+/// Cold attaches on NB-IoT can take several minutes.
+const ONLINE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Time without registration before the modem is recovered.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Modem recoveries attempted before giving up on finding a network.
+const SEARCH_RECOVERIES: u32 = 3;
+
+/// Host used for the startup connectivity check and HTTP demo.
+const CHECK_HOST: &str = "httpbin.org";
+
+/// Connectivity checks made before reporting the check as failed; the
+/// data path is often not usable right after the context comes up.
+const CHECK_ATTEMPTS: u32 = 3;
+
+/// DNS server used when the network does not provide one.
+const FALLBACK_DNS: &[Ipv4Address] = &[Ipv4Address::new(1, 1, 1, 1)];
+
+/// Log collector (`host`, `port`) to mirror logs to with the `net-log`
+/// feature, or `None` to keep logs on the UART only.
+#[cfg(feature = "net-log")]
+const NET_LOG_COLLECTOR: Option<(&str, u16)> = None;
+
+/// APNs to try in order when activating the PDP context.
+/// Leave empty to use the APN provisioned on the SIM.
+const APNS: &[&str] = &[];
 
 bind_interrupts!(struct Irqs {
-	SERIAL0 => uarte::InterruptHandler<peripherals::SERIAL0>;
+	SERIAL0 => buffered_uarte::InterruptHandler<peripherals::SERIAL0>;
 });
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
 	/* Initialize embassy-nrf peripherals and related libraries */
 	let p = embassy_nrf::init(Default::default());
+	let pins = board_pins!(p);
 
 	/*  */
 	let mut config = uarte::Config::default();
@@ -34,8 +95,26 @@ async fn main(spawner: Spawner) {
 	config.parity = uarte::Parity::EXCLUDED;
 	config.baudrate = uarte::Baudrate::BAUD115200;
 
-	let mut uart = Uarte::new(p.SERIAL0, p.P0_26, p.P0_27, Irqs, config);
-	let mut led = Output::new(p.P0_00, Level::Low, OutputDrive::Standard);
+	static LOG_UART_RX_BUF: StaticCell<[u8; 256]> = StaticCell::new();
+	static LOG_UART_TX_BUF: StaticCell<[u8; 1024]> = StaticCell::new();
+	let log_uart = BufferedUarte::new(
+		p.SERIAL0,
+		p.TIMER0,
+		p.PPI_CH0,
+		p.PPI_CH1,
+		p.PPI_GROUP0,
+		pins.console_rx,
+		pins.console_tx,
+		Irqs,
+		config,
+		LOG_UART_RX_BUF.init([0u8; 256]),
+		LOG_UART_TX_BUF.init([0u8; 1024]),
+	);
+	let (console_rx, log_tx) = log_uart.split();
+
+	static LOGGER: StaticCell<UartLogger> = StaticCell::new();
+	let uart: &'static UartLogger = LOGGER.init(SharedLogger::new(log_tx));
+	let led = Output::new(pins.led, Level::Low, OutputDrive::Standard);
 
 	log!(uart, "");
 	log!(uart, "        ___     ,~~.");
@@ -55,18 +134,43 @@ async fn main(spawner: Spawner) {
 	log!(uart, "       /////////////\\   _||_");
 	log!(uart, "");
 
+	let (reset_reason, reset_bits) = reset::take_reset_reason();
+	log!(
+		uart,
+		"Reset reason: {} (0x{:02x})",
+		reset_reason.as_str(),
+		reset_bits
+	);
+
 	// Startup LED indication
-	for _ in 0..3 {
-		led.set_high();
-		Timer::after_millis(100).await;
-		led.set_low();
-		Timer::after_millis(100).await;
+	match status_led::status_led_task(led) {
+		Ok(token) => spawner.spawn(token),
+		Err(_) => log!(uart, "Status LED unavailable: task spawn failed"),
 	}
+	status_led::set_pattern(LedPattern::Startup);
 
-	// Initialize modem with trace forwarding to UART1 (P0.29 TX at 1 Mbaud)
-	// TX: P0.29 - Available as VCOM1 through USB
+	// Load deployment configuration from flash
+	let mut nvmc = Nvmc::new(p.NVMC);
+	let device_config = match config::load_config(&mut nvmc) {
+		Ok(c) => c,
+		Err(e) => {
+			log!(uart, "Stored config invalid ({:?}), using defaults", e);
+			config::Config::default()
+		}
+	};
+
+	// Initialize modem with trace forwarding to UART1 at 1 Mbaud, on the
+	// board's trace pin
 	log!(uart, "Initializing modem with traces...");
-	let (device, control) = match modem::init_with_trace(&spawner, p.SERIAL1, p.P0_29).await {
+	let (device, control, _modem_tasks) = match modem::init_with_trace(
+		&spawner,
+		p.SERIAL1,
+		pins.trace,
+		CeregMode::Psm,
+		modem::MONITOR_POLL_INTERVAL,
+	)
+	.await
+	{
 		Ok(result) => result,
 		Err(e) => {
 			log!(uart, "FATAL: Modem init failed: {:?}", e);
@@ -75,9 +179,24 @@ async fn main(spawner: Spawner) {
 	};
 	log!(uart, "Modem ready (traces on UART1 @ 1Mbaud)!");
 
+	// Modem fingerprint for field debugging
+	if logger::DEBUG_ENABLED {
+		log_modem_fingerprint(control, uart).await;
+	}
+
+	// Accept AT commands typed on the log UART
+	match console::console_task(console_rx, control, uart) {
+		Ok(token) => spawner.spawn(token),
+		Err(_) => log!(uart, "Console unavailable: task spawn failed"),
+	}
+
 	// Initialize network stack
 	log!(uart, "Initializing network stack...");
-	let stack = match network::init(&spawner, device).await {
+	let mut imei_buf = [0u8; 64];
+	let seed = network::seed_from_imei(
+		modem::get_imei(control, &mut imei_buf).await.unwrap_or(""),
+	);
+	let stack = match network::init(&spawner, device, seed).await {
 		Ok(s) => s,
 		Err(e) => {
 			log!(uart, "FATAL: Network init failed: {:?}", e);
@@ -86,135 +205,224 @@ async fn main(spawner: Spawner) {
 	};
 	log!(uart, "Network stack initialized!");
 
+	// Recoverable failures cycle the modem and retry instead of halting
+	let mut retry = RetryPolicy::new(
+		Backoff::new(Duration::from_secs(10), Duration::from_secs(900))
+			.with_jitter(20, seed),
+	);
+
 	// Enable modem radio
 	log!(uart, "");
 	log!(uart, "Enabling modem (CFUN=1)...");
-	if let Err(e) = modem::enable(control).await {
+	if let Err(e) = retry.run(control, || modem::enable(control)).await {
 		log!(uart, "FATAL: Failed to enable modem: {:?}", e);
 		fatal_error!("Modem enable (CFUN=1) failed");
 	}
 	log!(uart, "Modem enabled");
 
+	// Stop early with a distinct LED pattern if there is no SIM
+	match modem::check_sim(control).await {
+		Ok(status) => log!(uart, "SIM status: {:?}", status),
+		Err(_) => {
+			log!(uart, "FATAL: No SIM card inserted");
+			status_led::set_pattern(LedPattern::NoSim);
+			core::future::pending::<()>().await;
+		}
+	}
+
 	Timer::after_millis(500).await;
 
-	// Wait for network registration
+	// Wait for registration and the data connection
 	log!(uart, "");
-	log!(uart, "Waiting for network registration...");
+	log!(uart, "Waiting for network (registration + PDP context)...");
+
+	// A provisioned APN takes precedence over the built-in list
+	let provisioned_apn = [device_config.apn.as_str()];
+	let apns = if device_config.apn.is_empty() {
+		APNS
+	} else {
+		&provisioned_apn[..]
+	};
+	let pdp_config = &pdp::PdpConfig { apns, auth: None };
+	let online = retry
+		.run(control, || async move {
+			// Also brings the modem back online after a retry
+			modem::enable(control).await?;
+			let result = pdp::wait_online_with_config(
+				control,
+				stack,
+				pdp_config,
+				ONLINE_TIMEOUT,
+			)
+			.await;
+			if let Err(e) = result {
+				log!(uart, "Failed to get online: {}", e);
+			}
+			result
+		})
+		.await;
+	match online {
+		Ok(ip_config) => {
+			log!(uart, "Network registered!");
+			if let Some(attach) = connectivity_stats().last_attach_duration() {
+				log!(uart, "Attach time: {} ms", attach.as_millis());
+			}
+			log!(uart, "IP address: {}", network::ipv4_str(ip_config.address));
+
+			// Remember the network for a faster reattach after sleep
+			if let Ok(op) = operator::get_operator(control).await {
+				log!(
+					uart,
+					"Operator: {} ({})",
+					op.plmn,
+					op.name.as_deref().unwrap_or("?")
+				);
+				operator::remember_plmn(&op.plmn);
+			}
+		}
+		Err(e) => {
+			log!(uart, "FATAL: Failed to get online: {:?}", e);
+			fatal_error!("Failed to get online");
+		}
+	}
+	log!(uart, "Network ready!");
 
-	loop {
-		// Wait for registration status change (non-polling, event-driven)
-		let status = wait_for_status_change().await;
+	// Check DNS and TCP reachability before using the connection
+	log!(uart, "");
+	log!(uart, "Checking connectivity ({})...", CHECK_HOST);
 
-		// Log status change
-		log!(uart, "CEREG: {}", status.as_str());
+	// Network did not provide a resolver: fall back to a public one until
+	// the next configuration from the network
+	if network::get_ipv4_config(stack).is_some_and(|config| config.dns_servers.is_empty()) {
+		network::set_current_dns_servers(stack, FALLBACK_DNS);
+	}
 
-		// Visual feedback
-		led.set_high();
-		Timer::after_millis(100).await;
-		led.set_low();
+	// Mirror logs off-device from here on
+	#[cfg(feature = "net-log")]
+	if let Some((host, port)) = NET_LOG_COLLECTOR {
+		match net_log::start(&spawner, stack, host, port) {
+			Ok(()) => log!(uart, "Mirroring logs to {}:{}", host, port),
+			Err(e) => log!(uart, "Log mirroring unavailable: {:?}", e),
+		}
+	}
 
-		// Handle registration success
-		if status.is_registered() {
-			log!(uart, "");
-			log!(uart, "Network registered!");
+	let mut check_backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(30));
+	let mut check = network::connectivity_check(stack, CHECK_HOST, 80).await;
+	for _ in 1..CHECK_ATTEMPTS {
+		if check.is_ok() {
 			break;
 		}
+		Timer::after(check_backoff.next_delay()).await;
+		check = network::connectivity_check(stack, CHECK_HOST, 80).await;
 	}
 
-	// Wait for network stack to get IP config
-	log!(uart, "");
-	log!(uart, "Activating PDP context (data connection)...");
-
-	// Activate PDP context and configure network stack
-	let _ip = match pdp::activate(control).await {
-		Ok(ip) => {
-			log!(uart, "PDP context activated!");
-			pdp::configure_stack(stack, ip, None);
-			log!(uart, "IP address: {}", ip);
-			ip
+	let remote_endpoint = match check {
+		Ok(endpoint) => {
+			log!(
+				uart,
+				"Connectivity OK ({} -> {})",
+				CHECK_HOST,
+				endpoint.addr
+			);
+			Some(endpoint)
+		}
+		Err(Error::InvalidResponse) => {
+			log!(
+				uart,
+				"Connectivity check failed: DNS resolution of {} failed",
+				CHECK_HOST
+			);
+			None
 		}
 		Err(e) => {
-			log!(uart, "FATAL: PDP activation failed: {:?}", e);
-			fatal_error!("PDP context activation failed");
+			log!(uart, "Connectivity check failed: {}", e);
+			None
 		}
 	};
 
-	// Wait for stack configuration
-	network::wait_for_config(stack).await;
-	log!(uart, "Network ready!");
-
 	// Demonstrate TCP socket connection
-	log!(uart, "");
-	log!(uart, "Testing TCP connection...");
-
-	// Socket buffers
-	let mut rx_buffer = [0u8; 1024];
-	let mut tx_buffer = [0u8; 1024];
-
-	let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
-	socket.set_timeout(Some(embassy_time::Duration::from_secs(10)));
-
-	// Connect to httpbin.org (IP: 54.208.105.16) port 80
-	// Note: For production, use DNS resolution
-	let remote_endpoint = embassy_net::IpEndpoint::new(
-		embassy_net::IpAddress::v4(54, 208, 105, 16), // httpbin.org
-		80,
-	);
-
-	log!(uart, "Connecting to httpbin.org:80...");
-	match socket.connect(remote_endpoint).await {
-		Ok(()) => {
-			log!(uart, "Connected!");
-
-			// Send HTTP GET request
-			let request = b"GET /ip HTTP/1.1\r\n\
-				Host: httpbin.org\r\n\
-				Connection: close\r\n\r\n";
-
-			log!(uart, "Sending HTTP request...");
-
-			// Write all data
-			let mut written = 0;
-			while written < request.len() {
-				match socket.write(&request[written..]).await {
-					Ok(0) => {
-						log!(uart, "Write error: connection closed");
-						break;
-					}
-					Ok(n) => written += n,
-					Err(e) => {
-						log!(uart, "Write error: {:?}", e);
-						break;
-					}
-				}
-			}
-
-			if written == request.len() {
-				log!(uart, "Request sent, reading response...");
-
-				// Read response
-				let mut response_buf = [0u8; 512];
-				match socket.read(&mut response_buf).await {
-					Ok(0) => log!(uart, "Connection closed by server"),
-					Ok(n) => {
-						if let Ok(response) =
-							core::str::from_utf8(&response_buf[..n])
+	if let Some(remote_endpoint) = remote_endpoint {
+		log!(uart, "");
+		log!(uart, "Testing TCP connection...");
+
+		// Socket buffers
+		let mut rx_buffer = [0u8; 1024];
+		let mut tx_buffer = [0u8; 1024];
+
+		let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+		socket.set_timeout(Some(embassy_time::Duration::from_secs(10)));
+
+		log!(uart, "Connecting to httpbin.org:80...");
+		match network::tcp_connect(&mut socket, remote_endpoint).await {
+			Ok(()) => {
+				log!(uart, "Connected!");
+
+				// Send HTTP GET request
+				let request = b"GET /ip HTTP/1.1\r\n\
+					Host: httpbin.org\r\n\
+					Connection: close\r\n\r\n";
+
+				log!(uart, "Sending HTTP request...");
+
+				let mut transfer = network::TransferStats::start();
+				match transfer.write_all(&mut socket, request).await {
+					Ok(()) => {
+						log!(uart, "Request sent, reading response...");
+
+						// Read response
+						let mut response_buf = [0u8; 512];
+						match transfer
+							.read(&mut socket, &mut response_buf)
+							.await
 						{
-							log!(uart, "Response ({} bytes):", n);
-							// Print first few lines of response
-							for line in response.lines().take(10) {
-								log!(uart, "  {}", line);
+							Ok(0) => log!(
+								uart,
+								"Connection closed by server"
+							),
+							Ok(n) => {
+								if let Ok(response) =
+									core::str::from_utf8(
+										&response_buf[..n],
+									) {
+									log!(
+										uart,
+										"Response ({} bytes):",
+										n
+									);
+									// Print first few lines of response
+									for line in response
+										.lines()
+										.take(10)
+									{
+										log!(
+											uart,
+											"  {}",
+											line
+										);
+									}
+								}
 							}
+							Err(e) => log!(uart, "Read error: {}", e),
 						}
 					}
-					Err(e) => log!(uart, "Read error: {:?}", e),
+					Err(e) => log!(uart, "Write error: {}", e),
 				}
-			}
 
-			socket.close();
-		}
-		Err(e) => {
-			log!(uart, "Connection failed: {:?}", e);
+				transfer.finish();
+				log!(
+					uart,
+					"Transfer: {} B sent, {} B received in {} ms ({} B/s)",
+					transfer.bytes_sent,
+					transfer.bytes_received,
+					transfer.duration().as_millis(),
+					transfer.throughput()
+				);
+
+				socket.close();
+			}
+			Err(e) => {
+				log!(uart, "Connection failed: {}", e);
+			}
 		}
 	}
 
@@ -222,17 +430,78 @@ async fn main(spawner: Spawner) {
 	log!(uart, "");
 	log!(uart, "Application running. Monitoring registration...");
 
+	let mut search_watchdog = SearchWatchdog::new(SEARCH_TIMEOUT, SEARCH_RECOVERIES);
 	loop {
-		// Monitor for registration changes
-		let status = wait_for_status_change().await;
-		log!(uart, "Registration changed: {}", status.as_str());
+		// Monitor for registration changes, recovering if searching too long
+		let status = match search_watchdog.deadline() {
+			Some(deadline) => {
+				with_deadline(deadline, wait_for_status_change()).await.ok()
+			}
+			None => Some(wait_for_status_change().await),
+		};
+
+		let Some(status) = status else {
+			log!(
+				uart,
+				"Not registered for {} s, recovering modem",
+				SEARCH_TIMEOUT.as_secs()
+			);
+			if let Err(e) = search_watchdog.recover(control).await {
+				log!(uart, "FATAL: Modem recovery failed: {:?}", e);
+				fatal_error!("No network after modem recovery");
+			}
 
-		led.set_high();
-		Timer::after_millis(100).await;
-		led.set_low();
+			// The modem came back without its data connection: bring it up
+			// and reconfigure the stack
+			match pdp::wake_and_ensure_connected(control, stack, pdp_config).await {
+				Ok(ip_config) => {
+					log!(
+						uart,
+						"Reconnected, IP address: {}",
+						network::ipv4_str(ip_config.address)
+					);
+					// Waiting for the attach may have taken the status change
+					if let Some(status) =
+						registration::query_registration(control).await
+					{
+						search_watchdog.update(status);
+					}
+				}
+				Err(e) => log!(uart, "Not reconnected after recovery: {:?}", e),
+			}
+			continue;
+		};
+
+		log!(uart, "Registration changed: {}", status.as_str());
+		status_led::set_pattern(LedPattern::Blink);
+		search_watchdog.update(status);
 
 		if !status.is_registered() {
 			log!(uart, "Warning: Lost network registration!");
 		}
 	}
 }
+
+/// Log the modem state at boot: system mode, functional mode, firmware,
+/// IMEI and ICCID. Debug level only.
+async fn log_modem_fingerprint(control: &Control<'_>, uart: &UartLogger) {
+	let mut buf = [0u8; 64];
+
+	log_debug!(uart, "--- Modem fingerprint ---");
+	let system_mode = modem::get_system_mode(control, &mut buf).await;
+	log_debug!(uart, "System mode: {}", system_mode.unwrap_or("?"));
+	match modem::get_functional_mode(control).await {
+		Ok(mode) => log_debug!(uart, "Functional mode: {}", mode),
+		Err(_) => log_debug!(uart, "Functional mode: ?"),
+	}
+	match modem::get_device_info(control).await {
+		Ok(info) => {
+			log_debug!(uart, "Firmware: {}", info.firmware_version);
+			log_debug!(uart, "IMEI: {}", info.imei);
+			log_debug!(uart, "ICCID: {}", info.iccid.as_deref().unwrap_or("no SIM"));
+			log_debug!(uart, "IMSI: {}", info.imsi.as_deref().unwrap_or("?"));
+		}
+		Err(e) => log_debug!(uart, "Device info unavailable: {:?}", e),
+	}
+	log_debug!(uart, "-------------------------");
+}
@@ -7,6 +7,50 @@
 //! Modem traces are forwarded to UART1 at 1 Mbaud.
 //! Use `init_with_trace()` to enable trace forwarding.
 //! Connect a trace tool to UART1 TX pin to capture modem debug output.
+//! The pin is given as a `TracePin`, which has a constructor per supported
+//! board so only a pin wired to the trace-capable UART can be picked.
+//!
+//! ## Task Teardown
+//! The init functions return `TaskHandles` for the trace and registration
+//! monitor tasks. `TaskHandles::shutdown()` stops them in a fixed order
+//! (monitor first, then traces) and waits for each to exit, so no stale
+//! task keeps sending AT commands while the modem is reconfigured. The
+//! modem runner task is not stopped, since `Control` depends on it.
+//! `TaskHandles::restart()` spawns the stopped tasks again afterwards; the
+//! init functions themselves can only run once.
+//!
+//! ## Registration Polling
+//! The registration monitor re-queries `AT+CEREG?` every `monitor_poll`
+//! (`MONITOR_POLL_INTERVAL` by default), since the driver does not deliver
+//! `+CEREG` URCs. Each poll wakes the modem, which works against PSM. Pass
+//! `None` to the init functions to only query once at startup; the
+//! application then must refresh the status itself, e.g. with
+//! `registration::RegistrationMonitor::query_status()` after waking.
+//! Without that, everything waiting for a registration change (attach,
+//! getting online, the search watchdog) waits forever.
+//!
+//! ## Modem Faults
+//! `check_alive()` probes the modem with a bounded `AT` command, and the
+//! registration monitor bounds its status queries the same way. An
+//! unresponsive modem is reported as faulted through `MODEM_FAULT_SIGNAL`,
+//! and from then on the trace task also captures the trace stream (which
+//! carries the modem coredump) so it can be read with `capture_coredump()`
+//! and forwarded off-device for analysis. The fault is not cleared: the
+//! driver cannot restart the modem, so recovering takes a system reset.
+//!
+//! ## Persistent Settings
+//! Some settings only take effect after a functional mode cycle, and are
+//! easy to get silently wrong. `apply_and_verify()` cycles CFUN around
+//! the set command and reads the value back, returning `Error::Config` if
+//! it did not stick. `set_system_mode()` and `set_band_lock()` use it.
+//!
+//! What persists across modem resets:
+//! - System mode (`%XSYSTEMMODE`): stored in modem NVM on CFUN=0.
+//! - Band lock (`%XBANDLOCK=1`, permanent): stored in modem NVM on CFUN=0.
+//!   The runtime lock (`%XBANDLOCK=2`) is lost on reset and is not used.
+//! - PSM timers: the requested values are sent on each attach and the
+//!   network decides what is granted, so check
+//!   `registration::negotiated_psm()` rather than reading back `+CPSMS`.
 //!
 //! ## Error Handling
 //! Functions return `Result<T, Error>` where errors should be handled
@@ -16,21 +60,31 @@
 
 use crate::error::{Error, Result};
 
+use core::cell::RefCell;
+use core::fmt::Write as _;
 use core::mem::MaybeUninit;
-use core::ptr::addr_of_mut;
 use core::slice;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
 use embassy_net_nrf91::{Control, NetDriver, Runner, State, TraceBuffer, TraceReader};
 use embassy_nrf::buffered_uarte::{self, BufferedUarteTx};
-use embassy_nrf::gpio::Pin;
+use embassy_nrf::gpio::{AnyPin, Pin};
 use embassy_nrf::interrupt;
 use embassy_nrf::uarte::Baudrate;
 use embassy_nrf::{bind_interrupts, peripherals, uarte, Peri};
-use embassy_time::Timer;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use heapless::{String, Vec};
 use static_cell::StaticCell;
 
-use crate::registration::RegistrationMonitor;
+use crate::at_builder::AtCommandBuilder;
+use crate::at_queue::{self, AtResponse};
+use crate::pdp;
+use crate::registration::{self, CeregMode, RegistrationMonitor, RegistrationStatus};
 
 // External symbols for IPC memory region (defined in memory.x)
 unsafe extern "C" {
@@ -49,8 +103,134 @@ bind_interrupts!(struct TraceIrqs {
 	SERIAL1 => buffered_uarte::InterruptHandler<peripherals::SERIAL1>;
 });
 
-// Static buffer for trace UART TX
-static mut TRACE_UART_BUF: [u8; 4096] = [0u8; 4096];
+/// Size of the trace UART TX buffer.
+const TRACE_UART_BUF_SIZE: usize = 4096;
+
+/// Size of the buffer holding trace data captured after a modem fault.
+const COREDUMP_BUF_SIZE: usize = 8192;
+
+/// Default time allowed for a single AT command to complete.
+pub const AT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum time the modem firmware has to come up during init.
+///
+/// Missing modem firmware or broken IPC otherwise hangs init forever.
+pub const INIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum time allowed for `shutdown` to detach from the network.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time the modem has to answer a liveness probe before it is considered faulted.
+const ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time the SIM has to report in after `enable` before it is considered
+/// missing. The SIM is powered up with CFUN=1 and `AT+CPIN?` reports
+/// `+CME ERROR: 10` until it has been read.
+const SIM_DETECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval at which `check_sim` re-queries the SIM status.
+const SIM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default interval at which the registration monitor re-queries CEREG.
+pub const MONITOR_POLL_INTERVAL: Option<Duration> = Some(Duration::from_secs(30));
+
+/// Signal raised when the modem is detected as faulted.
+pub static MODEM_FAULT_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+// Set once a fault is detected; enables coredump capture in the trace task
+static MODEM_FAULTED: AtomicBool = AtomicBool::new(false);
+
+// Trace data captured after the fault
+static COREDUMP: Mutex<CriticalSectionRawMutex, RefCell<Vec<u8, COREDUMP_BUF_SIZE>>> =
+	Mutex::new(RefCell::new(Vec::new()));
+
+/// Shutdown of the registration monitor task.
+static MONITOR_SHUTDOWN: TaskShutdown = TaskShutdown::new();
+
+/// Shutdown of the trace task.
+static TRACE_SHUTDOWN: TaskShutdown = TaskShutdown::new();
+
+/// UART and trace reader handed over by the stopped trace task, for
+/// `TaskHandles::restart()`.
+static PARKED_TRACE: Mutex<
+	CriticalSectionRawMutex,
+	RefCell<Option<(BufferedUarteTx<'static>, TraceReader<'static>)>>,
+> = Mutex::new(RefCell::new(None));
+
+/// Stop request for a task, and its acknowledgement.
+pub struct TaskShutdown {
+	request: Signal<CriticalSectionRawMutex, ()>,
+	stopped: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl TaskShutdown {
+	const fn new() -> Self {
+		Self {
+			request: Signal::new(),
+			stopped: Signal::new(),
+		}
+	}
+
+	/// Ask the task to stop and wait until it has exited.
+	pub async fn stop(&self) {
+		self.request.signal(());
+		self.stopped.wait().await;
+	}
+}
+
+/// Handles to the modem tasks that can be stopped and restarted, returned
+/// by the init functions.
+pub struct TaskHandles {
+	control: &'static Control<'static>,
+	monitor: &'static TaskShutdown,
+	cereg_mode: CeregMode,
+	monitor_poll: Option<Duration>,
+	trace: Option<&'static TaskShutdown>,
+}
+
+impl TaskHandles {
+	/// Stop the registration monitor, then the trace task.
+	///
+	/// Waits for each task to exit before stopping the next. The monitor
+	/// only stops between status queries, so this can take as long as one
+	/// AT command. Modem trace output is turned off before the trace task
+	/// stops so trace data does not pile up in the driver.
+	pub async fn shutdown(&self) {
+		self.monitor.stop().await;
+
+		if let Some(trace) = self.trace {
+			let _ = at_command_ok(self.control, "AT%XMODEMTRACE=0").await;
+			trace.stop().await;
+		}
+	}
+
+	/// Respawn the tasks stopped by `shutdown()`, in reverse order.
+	///
+	/// Call once the modem has been reconfigured, e.g. after a functional
+	/// mode cycle. The trace task gets back the UART and trace reader it
+	/// handed over when it stopped, and modem trace output is turned on
+	/// again.
+	///
+	/// # Errors
+	/// Returns `Error::TaskSpawn` if a task is still running (`shutdown()`
+	/// was not called) or cannot be spawned.
+	pub async fn restart(&self, spawner: &Spawner) -> Result<()> {
+		if self.trace.is_some() {
+			let (uart, reader) = PARKED_TRACE
+				.lock(|parked| parked.borrow_mut().take())
+				.ok_or(Error::TaskSpawn)?;
+			let token = trace_task(uart, reader).map_err(|_| Error::TaskSpawn)?;
+			spawner.spawn(token);
+			let _ = at_command_ok(self.control, "AT%XMODEMTRACE=1,2").await;
+		}
+
+		let token =
+			registration_monitor_task(self.control, self.cereg_mode, self.monitor_poll)
+				.map_err(|_| Error::TaskSpawn)?;
+		spawner.spawn(token);
+		Ok(())
+	}
+}
 
 /// Task to run the modem driver.
 ///
@@ -64,11 +244,27 @@ pub async fn modem_runner_task(runner: Runner<'static>) -> ! {
 /// Task to forward modem traces to UART1.
 ///
 /// Reads trace data from the modem and writes it to UART at 1 Mbaud.
+/// After a modem fault the trace data is also captured for
+/// `capture_coredump()`. Runs until stopped through `TaskHandles`, which
+/// can restart it with the same UART and reader.
 #[embassy_executor::task]
-pub async fn trace_task(mut uart: BufferedUarteTx<'static>, reader: TraceReader<'static>) -> ! {
+pub async fn trace_task(mut uart: BufferedUarteTx<'static>, reader: TraceReader<'static>) {
 	let mut rx = [0u8; 1024];
 	loop {
-		let n = reader.read(&mut rx[..]).await;
+		let n = match select(reader.read(&mut rx[..]), TRACE_SHUTDOWN.request.wait()).await
+		{
+			Either::First(n) => n,
+			Either::Second(()) => break,
+		};
+
+		if MODEM_FAULTED.load(Ordering::Acquire) {
+			COREDUMP.lock(|dump| {
+				let mut dump = dump.borrow_mut();
+				let room = dump.capacity() - dump.len();
+				let _ = dump.extend_from_slice(&rx[..n.min(room)]);
+			});
+		}
+
 		// Write all data using inherent method
 		let mut offset = 0;
 		while offset < n {
@@ -78,25 +274,36 @@ pub async fn trace_task(mut uart: BufferedUarteTx<'static>, reader: TraceReader<
 			}
 		}
 	}
+
+	// Hand the UART and reader over for a restart
+	PARKED_TRACE.lock(|parked| *parked.borrow_mut() = Some((uart, reader)));
+	TRACE_SHUTDOWN.stopped.signal(());
 }
 
 /// Task to monitor CEREG registration status.
 ///
-/// This task enables CEREG URCs and monitors for registration
-/// status changes, signaling through REGISTRATION_SIGNAL.
+/// This task enables CEREG URCs in the preferred `mode` (falling back to
+/// status-only if rejected) and monitors for registration status changes,
+/// signaling through REGISTRATION_SIGNAL. The status is re-queried every
+/// `poll_interval`, or only once at startup if it is `None`. Runs until
+/// stopped through `TaskHandles`.
 #[embassy_executor::task]
-pub async fn registration_monitor_task(control: &'static Control<'static>) {
-	let mut monitor = RegistrationMonitor::new();
+pub async fn registration_monitor_task(
+	control: &'static Control<'static>,
+	mode: CeregMode,
+	poll_interval: Option<Duration>,
+) {
+	let mut monitor = RegistrationMonitor::with_mode(mode);
 
 	// Enable CEREG URCs
-	monitor.enable_urcs(control).await;
+	let _ = monitor.enable_urcs(control).await;
 	Timer::after_millis(100).await;
 
 	// Do initial query to get current status
 	monitor.query_status(control).await;
 
 	// Note: The nRF91 modem sends +CEREG URCs when status changes.
-	// With AT+CEREG=2, these are delivered automatically.
+	// With AT+CEREG=5, these are delivered automatically.
 	// The embassy-net-nrf91 driver's at_command interface may receive
 	// these as part of responses. For true event-driven handling,
 	// we'd need direct URC subscription which isn't exposed in the API.
@@ -105,44 +312,117 @@ pub async fn registration_monitor_task(control: &'static Control<'static>) {
 	// In a production system, you might use the network stack's
 	// built-in connectivity handling instead.
 
+	// Without polling there is nothing left to do but wait for shutdown
+	let Some(poll_interval) = poll_interval else {
+		MONITOR_SHUTDOWN.request.wait().await;
+		MONITOR_SHUTDOWN.stopped.signal(());
+		return;
+	};
+
 	// The task stays alive to handle any future monitoring needs
 	loop {
 		// Wait for external trigger or timeout
 		// In a real implementation with URC subscription, we'd await here
-		Timer::after_secs(30).await;
-		monitor.query_status(control).await;
+		// Only stop here, never in the middle of an AT command
+		if let Either::Second(()) =
+			select(Timer::after(poll_interval), MONITOR_SHUTDOWN.request.wait()).await
+		{
+			break;
+		}
+
+		// A query that does not complete means the modem stopped
+		// responding; no extra probe, so each poll costs one command
+		if with_timeout(ALIVE_TIMEOUT, monitor.query_status(control))
+			.await
+			.is_err()
+		{
+			report_fault();
+		}
 	}
+
+	MONITOR_SHUTDOWN.stopped.signal(());
 }
 
+/// The modem can only access IPC memory in the lowest 128K of RAM.
+const IPC_RAM_START: usize = 0x2000_0000;
+const IPC_RAM_END: usize = 0x2002_0000;
+
+/// Accepted size range of the IPC region.
+const IPC_MIN_LEN: usize = 8 * 1024;
+const IPC_MAX_LEN: usize = 128 * 1024;
+
 /// Get the IPC memory region from linker symbols.
 ///
+/// The region is checked before use: a wrong memory.x otherwise only shows
+/// up as `wait_init` never returning.
+///
+/// # Errors
+/// Returns `Error::ModemInit` if the region is empty, outside the lowest
+/// 128K of RAM, not word aligned, or not between 8K and 128K long.
+///
 /// # Safety
 /// This function reads from linker-defined symbols and creates
 /// a mutable slice from them. The caller must ensure the memory
-/// region is valid and not accessed elsewhere.
-pub unsafe fn get_ipc_memory() -> &'static mut [MaybeUninit<u8>] {
+/// region is not accessed elsewhere.
+pub unsafe fn get_ipc_memory() -> Result<&'static mut [MaybeUninit<u8>]> {
 	let ipc_start = &__start_ipc as *const u8 as *mut MaybeUninit<u8>;
 	let ipc_end = &__end_ipc as *const u8 as *mut MaybeUninit<u8>;
-	let ipc_len = ipc_end.offset_from(ipc_start) as usize;
-	slice::from_raw_parts_mut(ipc_start, ipc_len)
+
+	let (start, end) = (ipc_start as usize, ipc_end as usize);
+	if end <= start || start < IPC_RAM_START || end > IPC_RAM_END {
+		return Err(Error::ModemInit);
+	}
+	let ipc_len = end - start;
+	if start % 4 != 0 || ipc_len % 4 != 0 || !(IPC_MIN_LEN..=IPC_MAX_LEN).contains(&ipc_len) {
+		return Err(Error::ModemInit);
+	}
+
+	Ok(slice::from_raw_parts_mut(ipc_start, ipc_len))
+}
+
+/// Wait for the modem firmware to finish initializing.
+///
+/// # Errors
+/// Returns `Error::ModemInit` if the modem is not ready within `timeout`.
+pub async fn wait_ready(control: &Control<'_>, timeout: Duration) -> Result<()> {
+	with_timeout(timeout, control.wait_init())
+		.await
+		.map_err(|_| Error::ModemInit)
 }
 
 /// Initialize the modem and spawn required tasks.
 ///
-/// Returns tuple of (NetDriver for network stack, Control for AT commands).
+/// Returns tuple of (NetDriver for network stack, Control for AT commands,
+/// TaskHandles for teardown).
 /// This variant does not enable modem traces.
 ///
+/// With `monitor_poll` set to `None` the registration status is queried
+/// only once. Since the driver delivers no `+CEREG` URCs, waiters such as
+/// `registration::await_attach`, `pdp::wait_online_with_config` and
+/// `registration::SearchWatchdog` then see no later change unless the
+/// application calls `RegistrationMonitor::query_status()` itself.
+///
 /// # Arguments
 /// * `spawner` - Embassy spawner for task creation
+/// * `cereg_mode` - Preferred `+CEREG` verbosity; `CeregMode::Psm` is
+///   needed for `registration::negotiated_psm()`
+/// * `monitor_poll` - Registration poll interval, `None` to disable polling
 ///
 /// # Returns
-/// `Ok((NetDriver, Control))` on success, `Err(Error)` on failure
+/// `Ok((NetDriver, Control, TaskHandles))` on success, `Err(Error)` on
+/// failure
 ///
 /// # Errors
-/// Returns `Error::TaskSpawn` if task spawning fails.
-pub async fn init(spawner: &Spawner) -> Result<(NetDriver<'static>, &'static Control<'static>)> {
+/// Returns `Error::ModemInit` if the IPC memory region is misconfigured or
+/// the modem is not ready within `INIT_TIMEOUT`, or `Error::TaskSpawn` if
+/// task spawning fails.
+pub async fn init(
+	spawner: &Spawner,
+	cereg_mode: CeregMode,
+	monitor_poll: Option<Duration>,
+) -> Result<(NetDriver<'static>, &'static Control<'static>, TaskHandles)> {
 	// Get IPC memory
-	let ipc_mem = unsafe { get_ipc_memory() };
+	let ipc_mem = unsafe { get_ipc_memory() }?;
 
 	// Initialize the modem driver (without traces)
 	static STATE: StaticCell<State> = StaticCell::new();
@@ -158,40 +438,89 @@ pub async fn init(spawner: &Spawner) -> Result<(NetDriver<'static>, &'static Con
 	let control = CONTROL.init(control);
 
 	// Wait for modem to be ready
-	control.wait_init().await;
+	wait_ready(control, INIT_TIMEOUT).await?;
+
+	// Disable command echo so responses start with the result
+	let _ = at_command_ok(control, "ATE0").await;
+
+	// Serialize AT commands from here on
+	at_queue::start(spawner, control)?;
 
 	// Spawn registration monitor
-	let token = registration_monitor_task(control).map_err(|_| Error::TaskSpawn)?;
+	let token = registration_monitor_task(control, cereg_mode, monitor_poll)
+		.map_err(|_| Error::TaskSpawn)?;
 	spawner.spawn(token);
 
-	Ok((device, control))
+	let tasks = TaskHandles {
+		control,
+		monitor: &MONITOR_SHUTDOWN,
+		cereg_mode,
+		monitor_poll,
+		trace: None,
+	};
+	Ok((device, control, tasks))
+}
+
+/// Baud rate expected by the modem trace tools.
+const TRACE_BAUDRATE: Baudrate = Baudrate::BAUD1M;
+
+/// TX pin for modem trace output.
+///
+/// Construct it with the function for your board; the pin type is checked at
+/// compile time, so a pin that is not wired to the trace UART is rejected.
+pub struct TracePin {
+	pin: Peri<'static, AnyPin>,
+}
+
+impl TracePin {
+	/// nRF9151-DK: P0.29, routed to VCOM1 on the USB interface MCU.
+	pub fn nrf9151_dk(pin: Peri<'static, peripherals::P0_29>) -> Self {
+		Self { pin: pin.into() }
+	}
+
+	/// Custom board: any pin.
+	///
+	/// `board_pins!` takes it from the same peripherals as the console
+	/// pins, so a pin already used by the console UART does not compile.
+	pub fn custom(pin: Peri<'static, impl Pin>) -> Self {
+		Self { pin: pin.into() }
+	}
 }
 
 /// Initialize the modem with trace forwarding to UART1.
 ///
-/// Modem traces will be output on UART1 TX pin at 1 Mbaud.
+/// Modem traces will be output on UART1 TX pin at 1 Mbaud. See `init` for
+/// what a `monitor_poll` of `None` means for registration waiters.
 ///
 /// # Arguments
 /// * `spawner` - Embassy spawner for task creation
 /// * `serial1` - SERIAL1 peripheral for trace UART
-/// * `trace_tx_pin` - TX pin for trace output (typically P0.01 on DK)
+/// * `trace_pin` - TX pin for trace output, see `TracePin`
+/// * `cereg_mode` - Preferred `+CEREG` verbosity, see `init`
+/// * `monitor_poll` - Registration poll interval, `None` to disable polling
 ///
 /// # Returns
-/// `Ok((NetDriver, Control))` on success, `Err(Error)` on failure
+/// `Ok((NetDriver, Control, TaskHandles))` on success, `Err(Error)` on
+/// failure
 ///
 /// # Errors
-/// Returns `Error::TaskSpawn` if task spawning fails.
+/// Returns `Error::ModemInit` if the IPC memory region is misconfigured or
+/// the modem is not ready within `INIT_TIMEOUT`, or `Error::TaskSpawn` if
+/// task spawning fails.
 pub async fn init_with_trace(
 	spawner: &Spawner,
 	serial1: Peri<'static, peripherals::SERIAL1>,
-	trace_tx_pin: Peri<'static, impl Pin>,
-) -> Result<(NetDriver<'static>, &'static Control<'static>)> {
+	trace_pin: TracePin,
+	cereg_mode: CeregMode,
+	monitor_poll: Option<Duration>,
+) -> Result<(NetDriver<'static>, &'static Control<'static>, TaskHandles)> {
 	// Get IPC memory
-	let ipc_mem = unsafe { get_ipc_memory() };
+	let ipc_mem = unsafe { get_ipc_memory() }?;
 
 	// Initialize the modem driver with trace support
 	static STATE: StaticCell<State> = StaticCell::new();
 	static TRACE_BUF: StaticCell<TraceBuffer> = StaticCell::new();
+	static TRACE_UART_BUF: StaticCell<[u8; TRACE_UART_BUF_SIZE]> = StaticCell::new();
 
 	let (device, control, runner, trace_reader) = embassy_net_nrf91::new_with_trace(
 		STATE.init(State::new()),
@@ -202,12 +531,15 @@ pub async fn init_with_trace(
 
 	// Set up trace UART at 1 Mbaud
 	let mut trace_config = uarte::Config::default();
-	trace_config.baudrate = Baudrate::BAUD1M;
+	trace_config.baudrate = TRACE_BAUDRATE;
 
-	let trace_uart =
-		BufferedUarteTx::new(serial1, trace_tx_pin, TraceIrqs, trace_config, unsafe {
-			&mut *addr_of_mut!(TRACE_UART_BUF)
-		});
+	let trace_uart = BufferedUarteTx::new(
+		serial1,
+		trace_pin.pin,
+		TraceIrqs,
+		trace_config,
+		TRACE_UART_BUF.init([0u8; TRACE_UART_BUF_SIZE]),
+	);
 
 	// Spawn trace forwarding task
 	let token = trace_task(trace_uart, trace_reader).map_err(|_| Error::TaskSpawn)?;
@@ -222,19 +554,88 @@ pub async fn init_with_trace(
 	let control = CONTROL_TRACE.init(control);
 
 	// Wait for modem to be ready
-	control.wait_init().await;
+	wait_ready(control, INIT_TIMEOUT).await?;
+
+	// Disable command echo so responses start with the result
+	let _ = at_command_ok(control, "ATE0").await;
+
+	// Serialize AT commands from here on
+	at_queue::start(spawner, control)?;
 
 	// Enable modem trace output
 	let mut resp_buf = [0u8; 64];
-	let _ = control
-		.at_command(b"AT%XMODEMTRACE=1,2", &mut resp_buf)
-		.await;
+	let _ = at_command_bytes(control, b"AT%XMODEMTRACE=1,2", &mut resp_buf).await;
 
 	// Spawn registration monitor
-	let token = registration_monitor_task(control).map_err(|_| Error::TaskSpawn)?;
+	let token = registration_monitor_task(control, cereg_mode, monitor_poll)
+		.map_err(|_| Error::TaskSpawn)?;
 	spawner.spawn(token);
 
-	Ok((device, control))
+	let tasks = TaskHandles {
+		control,
+		monitor: &MONITOR_SHUTDOWN,
+		cereg_mode,
+		monitor_poll,
+		trace: Some(&TRACE_SHUTDOWN),
+	};
+	Ok((device, control, tasks))
+}
+
+/// Send a raw AT command and return the response.
+///
+/// This is the primitive the other AT helpers build on. Use it directly
+/// for commands that are not valid UTF-8 or contain control characters,
+/// such as credential writes with PEM payloads.
+///
+/// Once the AT command queue runs (see `at_queue`), commands go through
+/// it. Commands longer than `at_queue::AT_CMD_MAX`, such as credential
+/// writes, hold the queue while they are sent instead. With the
+/// `at-timing` feature the duration is recorded, see `at_stats`.
+///
+/// # Arguments
+/// * `control` - Modem control interface
+/// * `cmd` - AT command bytes (without trailing CR/LF)
+/// * `resp_buf` - Buffer to store the response
+///
+/// # Returns
+/// Number of bytes written to response buffer
+pub async fn at_command_bytes<'a>(control: &Control<'a>, cmd: &[u8], resp_buf: &mut [u8]) -> usize {
+	#[cfg(feature = "at-timing")]
+	let start = embassy_time::Instant::now();
+
+	let len = if !at_queue::is_running() {
+		control.at_command(cmd, resp_buf).await
+	} else if cmd.len() > at_queue::AT_CMD_MAX {
+		at_queue::at_command_held(control, cmd, resp_buf).await
+	} else {
+		at_queue::at_command(cmd, resp_buf).await.unwrap_or(0)
+	};
+
+	#[cfg(feature = "at-timing")]
+	crate::at_stats::record(cmd, start.elapsed());
+
+	len
+}
+
+/// Send an AT command, keeping the response in an `at_queue` pool buffer.
+///
+/// Saves the response buffer on the caller's stack; drop the response
+/// once parsed to return the buffer. With the `at-timing` feature the
+/// duration is recorded, see `at_stats`.
+///
+/// # Errors
+/// Returns `Error::Config` if `cmd` is longer than `at_queue::AT_CMD_MAX`;
+/// send such commands with `at_command_bytes`.
+pub async fn at_response<'a>(control: &Control<'a>, cmd: &str) -> Result<AtResponse> {
+	#[cfg(feature = "at-timing")]
+	let start = embassy_time::Instant::now();
+
+	let response = at_queue::request_with(control, cmd.as_bytes()).await;
+
+	#[cfg(feature = "at-timing")]
+	crate::at_stats::record(cmd.as_bytes(), start.elapsed());
+
+	response
 }
 
 /// Send an AT command and return the response.
@@ -247,7 +648,45 @@ pub async fn init_with_trace(
 /// # Returns
 /// Number of bytes written to response buffer
 pub async fn at_command<'a>(control: &Control<'a>, cmd: &str, resp_buf: &mut [u8]) -> usize {
-	control.at_command(cmd.as_bytes(), resp_buf).await
+	at_command_bytes(control, cmd.as_bytes(), resp_buf).await
+}
+
+/// Send an AT command, giving up after `timeout`.
+///
+/// # Returns
+/// Number of bytes written to response buffer.
+///
+/// # Errors
+/// Returns `Error::AtTimeout(cmd)` if the modem does not respond in time,
+/// so the failing command shows up in error logs.
+pub async fn at_command_timeout<'a>(
+	control: &Control<'a>,
+	cmd: &'static str,
+	resp_buf: &mut [u8],
+	timeout: Duration,
+) -> Result<usize> {
+	with_timeout(timeout, at_command(control, cmd, resp_buf))
+		.await
+		.map_err(|_| Error::AtTimeout(cmd))
+}
+
+/// Send an AT command with a timeout and check if response contains "OK".
+///
+/// # Returns
+/// `Ok(())` if response contains "OK", `Err(Error::AtTimeout(cmd))` if the
+/// modem did not respond in time, `Err(Error::AtCommand)` otherwise.
+pub async fn at_command_ok_timeout<'a>(
+	control: &Control<'a>,
+	cmd: &'static str,
+	timeout: Duration,
+) -> Result<()> {
+	let mut resp_buf = [0u8; 128];
+	let len = at_command_timeout(control, cmd, &mut resp_buf, timeout).await?;
+
+	match core::str::from_utf8(&resp_buf[..len]) {
+		Ok(resp) if resp.contains("OK") => Ok(()),
+		_ => Err(Error::AtCommand),
+	}
 }
 
 /// Send an AT command and check if response contains "OK".
@@ -270,39 +709,1158 @@ pub async fn at_command_ok<'a>(control: &Control<'a>, cmd: &str) -> Result<()> {
 
 /// Enable the modem (CFUN=1).
 ///
+/// Also starts the attach timer reported through
+/// `registration::connectivity_stats()`.
+///
 /// # Returns
-/// `Ok(())` on success, `Err(Error::AtCommand)` on failure.
+/// `Ok(())` on success, `Err(Error::AtTimeout)` if the modem did not
+/// respond, `Err(Error::AtCommand)` on other failures.
 pub async fn enable<'a>(control: &Control<'a>) -> Result<()> {
-	at_command_ok(control, "AT+CFUN=1").await
+	registration::mark_attach_start();
+	at_command_ok_timeout(control, "AT+CFUN=1", AT_TIMEOUT).await
 }
 
 /// Disable the modem (CFUN=0).
 ///
 /// # Returns
-/// `Ok(())` on success, `Err(Error::AtCommand)` on failure.
+/// `Ok(())` on success, `Err(Error::AtTimeout)` if the modem did not
+/// respond, `Err(Error::AtCommand)` on other failures.
 pub async fn disable<'a>(control: &Control<'a>) -> Result<()> {
-	at_command_ok(control, "AT+CFUN=0").await
+	at_command_ok_timeout(control, "AT+CFUN=0", AT_TIMEOUT).await
+}
+
+/// Cleanly detach from the network before the MCU powers down.
+///
+/// Deactivates the PDP context, sends CFUN=0 and waits until the modem
+/// reports it is no longer registered. Safe to call when already shut
+/// down.
+///
+/// # Returns
+/// `Ok(())` once detached, `Err(Error::AtCommand)` if CFUN=0 is rejected,
+/// or `Err(Error::Timeout)` if the detach is not confirmed in time.
+pub async fn shutdown<'a>(control: &Control<'a>) -> Result<()> {
+	let sequence = async {
+		// Fails harmlessly if no context is active
+		let _ = pdp::deactivate(control).await;
+
+		disable(control).await?;
+
+		// Wait for the detach to be confirmed
+		loop {
+			match registration::query_registration(control).await {
+				Some(status) if !status.is_registered() => return Ok(()),
+				_ => Timer::after_millis(200).await,
+			}
+		}
+	};
+
+	with_timeout(SHUTDOWN_TIMEOUT, sequence)
+		.await
+		.map_err(|_| Error::Timeout)?
+}
+
+/// Cycle the modem functional mode: go offline (CFUN=0) and check that the
+/// modem still answers.
+///
+/// This is not a modem restart. The modem firmware keeps running, and the
+/// driver offers no way to re-initialize it short of resetting the
+/// application core. Going offline does drop network registration, the PDP
+/// context and with it the network stack IP configuration, so callers must
+/// re-run `enable`, wait for registration and re-activate the PDP context
+/// afterwards.
+///
+/// A hung modem does not answer CFUN=0 within `AT_TIMEOUT`; it is reported
+/// as faulted and stays so (see `is_faulted`), as only a system reset
+/// brings it back.
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::AtTimeout)` if the modem did not
+/// respond, or `Err(Error::AtCommand)` if it rejects CFUN=0.
+pub async fn cycle_functional_mode<'a>(control: &Control<'a>) -> Result<()> {
+	if let Err(e) = disable(control).await {
+		if matches!(e, Error::AtTimeout(_)) {
+			report_fault();
+		}
+		return Err(e);
+	}
+
+	// Give the modem time to detach and settle
+	Timer::after_millis(1000).await;
+
+	check_alive(control).await
+}
+
+/// Scope of a modem factory reset (`AT%XFACTORYRESET`).
+#[cfg(feature = "factory-reset")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FactoryResetKind {
+	/// Reset all modem data to factory settings
+	All,
+	/// Reset only user-configurable data (PDP contexts, PSM settings, etc.)
+	UserConfig,
+}
+
+/// Reset modem configuration to factory defaults.
+///
+/// Clears stored settings such as PDP contexts and PSM configuration,
+/// then cycles the functional mode. Only available with the
+/// `factory-reset` feature enabled, so it cannot be called by accident.
+///
+/// The same caveats as `cycle_functional_mode` apply: registration and the
+/// PDP context must be re-established afterwards.
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::AtCommand)` on failure.
+#[cfg(feature = "factory-reset")]
+pub async fn factory_reset<'a>(control: &Control<'a>, kind: FactoryResetKind) -> Result<()> {
+	// Factory reset is only accepted in functional mode 0
+	disable(control).await?;
+
+	let cmd = match kind {
+		FactoryResetKind::All => "AT%XFACTORYRESET=0",
+		FactoryResetKind::UserConfig => "AT%XFACTORYRESET=1",
+	};
+	at_command_ok(control, cmd).await?;
+
+	cycle_functional_mode(control).await
+}
+
+/// Check that the modem still answers AT commands.
+///
+/// If the modem does not respond within a bounded time it is reported as
+/// faulted: `MODEM_FAULT_SIGNAL` is raised and coredump capture starts.
+///
+/// # Returns
+/// `Ok(())` if the modem responded, `Err(Error::AtTimeout("AT"))` if it is
+/// faulted.
+pub async fn check_alive<'a>(control: &Control<'a>) -> Result<()> {
+	let mut resp_buf = [0u8; 32];
+	match at_command_timeout(control, "AT", &mut resp_buf, ALIVE_TIMEOUT).await {
+		Ok(_) => Ok(()),
+		Err(e) => {
+			report_fault();
+			Err(e)
+		}
+	}
+}
+
+/// Mark the modem as faulted and start capturing the coredump.
+pub fn report_fault() {
+	if !MODEM_FAULTED.swap(true, Ordering::AcqRel) {
+		COREDUMP.lock(|dump| dump.borrow_mut().clear());
+		MODEM_FAULT_SIGNAL.signal(());
+	}
+}
+
+/// Check whether a modem fault has been detected.
+pub fn is_faulted() -> bool {
+	MODEM_FAULTED.load(Ordering::Acquire)
+}
+
+/// Wait until a modem fault is detected.
+pub async fn wait_for_fault() {
+	MODEM_FAULT_SIGNAL.wait().await
+}
+
+/// Copy the captured coredump trace data into `out`.
+///
+/// Capture requires trace forwarding (`init_with_trace()`). Data keeps
+/// accumulating until the internal buffer is full, so call this after
+/// giving the modem time to emit its coredump.
+///
+/// # Returns
+/// Number of bytes copied into `out`.
+pub fn capture_coredump(out: &mut [u8]) -> usize {
+	COREDUMP.lock(|dump| {
+		let dump = dump.borrow();
+		let len = dump.len().min(out.len());
+		out[..len].copy_from_slice(&dump[..len]);
+		len
+	})
+}
+
+/// SIM card status from `AT+CPIN?`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimStatus {
+	/// SIM ready, no PIN required
+	Ready,
+	/// SIM PIN required
+	PinRequired,
+	/// SIM PUK required
+	PukRequired,
+	/// No SIM inserted (`+CME ERROR: 10`)
+	NotInserted,
+	/// Unrecognized response
+	Unknown,
+}
+
+/// Parse an `AT+CPIN?` response.
+pub fn parse_cpin(response: &str) -> SimStatus {
+	if response.contains("+CME ERROR: 10") {
+		return SimStatus::NotInserted;
+	}
+
+	let Some(pos) = response.find("+CPIN:") else {
+		return SimStatus::Unknown;
+	};
+	let value = response[pos + 6..].lines().next().unwrap_or("").trim();
+
+	match value {
+		"READY" => SimStatus::Ready,
+		"SIM PIN" => SimStatus::PinRequired,
+		"SIM PUK" => SimStatus::PukRequired,
+		_ => SimStatus::Unknown,
+	}
+}
+
+/// Query the SIM card status.
+///
+/// The SIM is only powered in functional mode 1 or 41, so call this after
+/// `enable`.
+pub async fn get_sim_status<'a>(control: &Control<'a>) -> SimStatus {
+	let mut resp_buf = [0u8; 64];
+	let len = at_command(control, "AT+CPIN?", &mut resp_buf).await;
+	core::str::from_utf8(&resp_buf[..len])
+		.map(parse_cpin)
+		.unwrap_or(SimStatus::Unknown)
+}
+
+/// Check that a SIM card is present.
+///
+/// Detects a missing SIM up front so it does not surface as an endless
+/// "Searching..." during registration. Right after `enable` the SIM may
+/// not have been read yet, so the status is polled for up to
+/// `SIM_DETECT_TIMEOUT` before the SIM is considered missing.
+///
+/// # Returns
+/// The SIM status if a SIM is present.
+///
+/// # Errors
+/// Returns `Error::Config` if no SIM is inserted.
+pub async fn check_sim<'a>(control: &Control<'a>) -> Result<SimStatus> {
+	let deadline = Instant::now() + SIM_DETECT_TIMEOUT;
+	loop {
+		match get_sim_status(control).await {
+			SimStatus::NotInserted | SimStatus::Unknown
+				if Instant::now() < deadline =>
+			{
+				Timer::after(SIM_POLL_INTERVAL).await
+			}
+			SimStatus::NotInserted => return Err(Error::Config),
+			status => return Ok(status),
+		}
+	}
+}
+
+/// Temperature threshold crossing reported by `temperature_monitor_task`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TemperatureEvent {
+	/// Temperature rose to or above the threshold (degrees Celsius)
+	High(i16),
+	/// Temperature fell back below the threshold (degrees Celsius)
+	Normal(i16),
+}
+
+/// Signal for modem temperature threshold crossings.
+pub static TEMPERATURE_SIGNAL: Signal<CriticalSectionRawMutex, TemperatureEvent> = Signal::new();
+
+/// Read the modem temperature in degrees Celsius (`AT%XTEMP?`).
+///
+/// # Returns
+/// `Ok(temperature)` on success, `Err(Error::InvalidResponse)` if the
+/// firmware does not support the command or the response is malformed.
+pub async fn get_temperature<'a>(control: &Control<'a>) -> Result<i16> {
+	let mut resp_buf = [0u8; 64];
+	let len = at_command(control, "AT%XTEMP?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	parse_prefixed_int(resp, "%XTEMP:").ok_or(Error::InvalidResponse)
+}
+
+/// Task monitoring the modem temperature.
+///
+/// Polls the temperature every `interval` and signals `TEMPERATURE_SIGNAL`
+/// when it crosses `threshold` (degrees Celsius) in either direction, so
+/// the application can throttle transmissions before thermal shutdown.
+/// Optional: spawn it only if thermal monitoring is needed.
+#[embassy_executor::task]
+pub async fn temperature_monitor_task(
+	control: &'static Control<'static>,
+	threshold: i16,
+	interval: Duration,
+) {
+	let mut high = false;
+	loop {
+		if let Ok(temperature) = get_temperature(control).await {
+			if temperature >= threshold && !high {
+				high = true;
+				TEMPERATURE_SIGNAL.signal(TemperatureEvent::High(temperature));
+			} else if temperature < threshold && high {
+				high = false;
+				TEMPERATURE_SIGNAL.signal(TemperatureEvent::Normal(temperature));
+			}
+		}
+		Timer::after(interval).await;
+	}
+}
+
+/// Battery voltage threshold crossing reported by `vbat_monitor_task`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VbatEvent {
+	/// Voltage dropped below the threshold (millivolts)
+	Low(u16),
+	/// Voltage recovered to or above the threshold (millivolts)
+	Normal(u16),
+}
+
+/// Signal for battery voltage threshold crossings.
+pub static VBAT_SIGNAL: Signal<CriticalSectionRawMutex, VbatEvent> = Signal::new();
+
+/// Read the supply voltage measured by the modem in millivolts (`AT%XVBAT`).
+///
+/// # Returns
+/// `Ok(millivolts)` on success, `Err(Error::InvalidResponse)` if the
+/// response is malformed.
+pub async fn get_vbat<'a>(control: &Control<'a>) -> Result<u16> {
+	let mut resp_buf = [0u8; 64];
+	let len = at_command(control, "AT%XVBAT", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	parse_prefixed_int(resp, "%XVBAT:").ok_or(Error::InvalidResponse)
+}
+
+/// Task monitoring the battery voltage.
+///
+/// Polls the voltage every `interval` and signals `VBAT_SIGNAL` when it
+/// crosses `threshold_mv` in either direction. The application can skip a
+/// transmission while the voltage is too low to sustain it without a
+/// brownout. Optional: spawn it only on battery-powered devices.
+#[embassy_executor::task]
+pub async fn vbat_monitor_task(
+	control: &'static Control<'static>,
+	threshold_mv: u16,
+	interval: Duration,
+) {
+	let mut low = false;
+	loop {
+		if let Ok(vbat) = get_vbat(control).await {
+			if vbat < threshold_mv && !low {
+				low = true;
+				VBAT_SIGNAL.signal(VbatEvent::Low(vbat));
+			} else if vbat >= threshold_mv && low {
+				low = false;
+				VBAT_SIGNAL.signal(VbatEvent::Normal(vbat));
+			}
+		}
+		Timer::after(interval).await;
+	}
+}
+
+/// LTE signal quality from `AT+CESQ`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignalQuality {
+	/// Reference signal received power in dBm (-140 to -44)
+	pub rsrp_dbm: i16,
+	/// Reference signal received quality in dB (-20 to -3), rounded down
+	pub rsrq_db: i8,
+}
+
+/// Parse an `AT+CESQ` response.
+///
+/// Format: `+CESQ: <rxlev>,<ber>,<rscp>,<ecno>,<rsrq>,<rsrp>`, where 255
+/// means not known (e.g. not camped on a cell).
+pub fn parse_cesq(response: &str) -> Option<SignalQuality> {
+	let value = extract_value(response, "+CESQ:")?;
+	let mut fields = value.split(',').map(str::trim).skip(4);
+	let rsrq: u8 = fields.next()?.parse().ok()?;
+	let rsrp: u8 = fields.next()?.parse().ok()?;
+	if rsrq > 34 || rsrp > 97 {
+		return None;
+	}
+
+	Some(SignalQuality {
+		rsrp_dbm: i16::from(rsrp) - 140,
+		// Index 0 is below -19.5 dB, then 0.5 dB steps up to -3 dB
+		rsrq_db: (rsrq / 2) as i8 - 20,
+	})
+}
+
+/// Read the current signal quality (`AT+CESQ`).
+///
+/// # Returns
+/// `Ok(quality)` on success, `Err(Error::InvalidResponse)` if the values
+/// are not known (not camped on a cell) or the response is malformed.
+pub async fn get_signal_quality<'a>(control: &Control<'a>) -> Result<SignalQuality> {
+	let mut resp_buf = [0u8; 64];
+	let len = at_command(control, "AT+CESQ", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	parse_cesq(resp).ok_or(Error::InvalidResponse)
+}
+
+/// Radio and cell details from `AT%XMONITOR`.
+///
+/// Only `registration` is reported while the modem is not registered; the
+/// other fields are `None` then, or when the modem reports them as unknown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RfStatus {
+	/// Registration status, as in `+CEREG`
+	pub registration: RegistrationStatus,
+	/// Full operator name, if the network provides one
+	pub full_name: Option<String<32>>,
+	/// Short operator name, if the network provides one
+	pub short_name: Option<String<32>>,
+	/// PLMN (MCC and MNC), e.g. `24201`
+	pub plmn: Option<String<6>>,
+	/// Tracking area code
+	pub tac: Option<u16>,
+	/// Access technology: 7 for LTE-M, 9 for NB-IoT
+	pub act: Option<u8>,
+	/// LTE band
+	pub band: Option<u8>,
+	/// E-UTRAN cell ID
+	pub cell_id: Option<u32>,
+	/// Physical cell ID
+	pub phys_cell_id: Option<u16>,
+	/// E-UTRA absolute radio frequency channel number
+	pub earfcn: Option<u32>,
+	/// Reference signal received power in dBm (-140 to -44)
+	pub rsrp_dbm: Option<i16>,
+	/// Signal-to-noise ratio in dB (-24 to 25)
+	pub snr_db: Option<i8>,
+	/// eDRX value granted by the network (4-bit, see `EdrxConfig`)
+	pub edrx: Option<u8>,
+	/// PSM active time (T3324) granted by the network
+	pub active_time: Option<Duration>,
+	/// PSM periodic TAU (T3412 extended) granted by the network
+	pub periodic_tau: Option<Duration>,
+}
+
+/// Split an AT response value at commas outside quoted strings.
+fn split_fields(value: &str) -> impl Iterator<Item = &str> {
+	let mut rest = Some(value);
+	core::iter::from_fn(move || {
+		let remaining = rest?;
+		let mut quoted = false;
+		for (i, c) in remaining.char_indices() {
+			match c {
+				'"' => quoted = !quoted,
+				',' if !quoted => {
+					rest = Some(&remaining[i + 1..]);
+					return Some(remaining[..i].trim());
+				}
+				_ => {}
+			}
+		}
+		rest = None;
+		Some(remaining.trim())
+	})
+}
+
+/// Parse an `AT%XMONITOR` response.
+///
+/// Format: `%XMONITOR: <reg_status>[,<full_name>,<short_name>,<plmn>,<tac>,
+/// <AcT>,<band>,<cell_id>,<phys_cell_id>,<EARFCN>,<rsrp>,<snr>,
+/// <NW-provided_eDRX_value>,<Active-Time>,<Periodic-TAU-ext>,<Periodic-TAU>]`,
+/// e.g. `%XMONITOR: 1,"Telia N","Telia N","24202","0901",7,20,"012BEEF3",
+/// 428,6400,53,35,"","11100000","00101001","00001001"`.
+///
+/// Strings are quoted, TAC and cell ID are hex, and the timers are 8-bit
+/// strings. RSRP 255 and SNR 127 mean not known.
+pub fn parse_xmonitor(response: &str) -> Option<RfStatus> {
+	let value = extract_value(response, "%XMONITOR:")?;
+	let mut fields = split_fields(value).map(|field| field.trim_matches('"'));
+
+	let reg_status = RegistrationStatus::from_u8(fields.next()?.parse().ok()?);
+	let mut next = || fields.next().filter(|field| !field.is_empty());
+
+	let full_name = next().and_then(|name| String::try_from(name).ok());
+	let short_name = next().and_then(|name| String::try_from(name).ok());
+	let plmn = next().and_then(|plmn| String::try_from(plmn).ok());
+	let tac = next().and_then(|tac| u16::from_str_radix(tac, 16).ok());
+	let act = next().and_then(|act| act.parse().ok());
+	let band = next()
+		.and_then(|band| band.parse().ok())
+		.filter(|&band| band != 0);
+	let cell_id = next().and_then(|id| u32::from_str_radix(id, 16).ok());
+	let phys_cell_id = next().and_then(|id| id.parse().ok());
+	let earfcn = next().and_then(|earfcn| earfcn.parse().ok());
+	let rsrp_dbm = next()
+		.and_then(|rsrp| rsrp.parse::<u8>().ok())
+		.filter(|&rsrp| rsrp <= 97)
+		.map(|rsrp| i16::from(rsrp) - 140);
+	let snr_db = next()
+		.and_then(|snr| snr.parse::<u8>().ok())
+		.filter(|&snr| snr <= 49)
+		.map(|snr| snr as i8 - 24);
+	let edrx = next().and_then(|edrx| u8::from_str_radix(edrx, 2).ok());
+	let active_time = next().and_then(registration::decode_active_time);
+	let periodic_tau = next().and_then(registration::decode_periodic_tau);
+
+	Some(RfStatus {
+		registration: reg_status,
+		full_name,
+		short_name,
+		plmn,
+		tac,
+		act,
+		band,
+		cell_id,
+		phys_cell_id,
+		earfcn,
+		rsrp_dbm,
+		snr_db,
+		edrx,
+		active_time,
+		periodic_tau,
+	})
+}
+
+/// Read the radio and cell details (`AT%XMONITOR`).
+///
+/// Also updates `registration::negotiated_psm()` with the granted timers.
+///
+/// # Returns
+/// `Ok(status)` on success, `Err(Error::InvalidResponse)` if the response is
+/// malformed.
+pub async fn get_rf_status<'a>(control: &Control<'a>) -> Result<RfStatus> {
+	let response = at_response(control, "AT%XMONITOR").await?;
+	let resp = core::str::from_utf8(&response).map_err(|_| Error::InvalidResponse)?;
+	let status = parse_xmonitor(resp).ok_or(Error::InvalidResponse)?;
+
+	registration::set_negotiated_psm(status.periodic_tau.zip(status.active_time));
+	Ok(status)
+}
+
+/// Parse the integer following `prefix` in a response, e.g. `%XTEMP: 29`.
+fn parse_prefixed_int<T: core::str::FromStr>(response: &str, prefix: &str) -> Option<T> {
+	extract_value(response, prefix)?.parse().ok()
+}
+
+/// Strip a leading command echo from an AT command response.
+///
+/// Echo is disabled with `ATE0` at init, but a reset or the console can
+/// turn it back on, so parsers should not rely on it being off.
+pub fn strip_echo(response: &str) -> &str {
+	let trimmed = response.trim_start();
+	let first_line_end = trimmed.find('\n').map_or(trimmed.len(), |i| i + 1);
+	let first_line = trimmed[..first_line_end].trim();
+	if first_line
+		.get(..2)
+		.is_some_and(|cmd| cmd.eq_ignore_ascii_case("AT"))
+	{
+		&trimmed[first_line_end..]
+	} else {
+		response
+	}
+}
+
+/// Extract the value from an AT command response.
+///
+/// Strips a leading command echo, skips blank lines and the final `OK`,
+/// then returns the value with `prefix` (e.g. `"+CGSN:"`) and surrounding
+/// quotes stripped. With an empty `prefix` the first response line is
+/// returned.
+///
+/// # Returns
+/// The trimmed value, or `None` if the response is an error or has no
+/// matching line.
+pub fn extract_value<'r>(response: &'r str, prefix: &str) -> Option<&'r str> {
+	for line in strip_echo(response).lines().map(str::trim) {
+		if line.is_empty() || line == "OK" {
+			continue;
+		}
+		if line.contains("ERROR") {
+			return None;
+		}
+		if let Some(value) = line.strip_prefix(prefix) {
+			return Some(value.trim().trim_matches('"'));
+		}
+	}
+	None
+}
+
+/// Send an AT command and copy the trimmed value from its response to
+/// `buf`.
+///
+/// # Returns
+/// The value, or `None` if there is none or it does not fit in `buf`.
+async fn query_value<'a, 'b>(
+	control: &Control<'a>,
+	cmd: &str,
+	prefix: &str,
+	buf: &'b mut [u8],
+) -> Option<&'b str> {
+	let response = at_response(control, cmd).await.ok()?;
+	let value = extract_value(core::str::from_utf8(&response).ok()?, prefix)?;
+
+	let dest = buf.get_mut(..value.len())?;
+	dest.copy_from_slice(value.as_bytes());
+	core::str::from_utf8(dest).ok()
 }
 
-/// Get modem firmware version.
+/// Get modem firmware version, e.g. `mfw_nrf91x1_2.0.1`.
 pub async fn get_firmware_version<'a, 'b>(
 	control: &Control<'a>,
 	buf: &'b mut [u8],
 ) -> Option<&'b str> {
-	let len = at_command(control, "AT+CGMR", buf).await;
-	if len > 0 {
-		core::str::from_utf8(&buf[..len]).ok()
-	} else {
-		None
-	}
+	query_value(control, "AT+CGMR", "", buf).await
 }
 
-/// Get IMEI.
+/// Get IMEI (digits only).
 pub async fn get_imei<'a, 'b>(control: &Control<'a>, buf: &'b mut [u8]) -> Option<&'b str> {
-	let len = at_command(control, "AT+CGSN", buf).await;
-	if len > 0 {
-		core::str::from_utf8(&buf[..len]).ok()
+	query_value(control, "AT+CGSN", "", buf).await
+}
+
+/// Get manufacturer, e.g. `Nordic Semiconductor ASA`.
+pub async fn get_manufacturer<'a, 'b>(control: &Control<'a>, buf: &'b mut [u8]) -> Option<&'b str> {
+	query_value(control, "AT+CGMI", "", buf).await
+}
+
+/// Get model, e.g. `nRF9151-LACA`.
+pub async fn get_model<'a, 'b>(control: &Control<'a>, buf: &'b mut [u8]) -> Option<&'b str> {
+	query_value(control, "AT+CGMM", "", buf).await
+}
+
+/// Get SIM ICCID (digits only). `None` if no SIM is inserted.
+pub async fn get_iccid<'a, 'b>(control: &Control<'a>, buf: &'b mut [u8]) -> Option<&'b str> {
+	query_value(control, "AT%XICCID", "%XICCID:", buf).await
+}
+
+/// Get SIM IMSI (digits only, `AT+CIMI`).
+///
+/// `None` if no SIM is inserted or the SIM is locked, e.g. the PIN has not
+/// been entered yet.
+pub async fn get_imsi<'a, 'b>(control: &Control<'a>, buf: &'b mut [u8]) -> Option<&'b str> {
+	query_value(control, "AT+CIMI", "", buf).await
+}
+
+/// Get the enabled system modes (`AT%XSYSTEMMODE?`).
+///
+/// Returns the raw `<LTE_M>,<NB_IoT>,<GNSS>,<preference>` value, e.g. `1,0,1,0`.
+pub async fn get_system_mode<'a, 'b>(control: &Control<'a>, buf: &'b mut [u8]) -> Option<&'b str> {
+	query_value(control, "AT%XSYSTEMMODE?", "%XSYSTEMMODE:", buf).await
+}
+
+/// Get the functional mode (`AT+CFUN?`), e.g. `1` for normal operation.
+///
+/// # Returns
+/// `Ok(mode)` on success, `Err(Error::InvalidResponse)` if the response is
+/// malformed.
+pub async fn get_functional_mode<'a>(control: &Control<'a>) -> Result<u8> {
+	let mut resp_buf = [0u8; 64];
+	let len = at_command(control, "AT+CFUN?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	parse_prefixed_int(resp, "+CFUN:").ok_or(Error::InvalidResponse)
+}
+
+/// Connection statistics collected by the modem (`AT%XCONNSTAT`).
+///
+/// The modem counts SMS and data since collection was started; it does
+/// not count connection attempts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConnStats {
+	/// SMS messages sent
+	pub sms_tx: u32,
+	/// SMS messages received
+	pub sms_rx: u32,
+	/// Data sent, in kilobytes
+	pub data_tx_kb: u32,
+	/// Data received, in kilobytes
+	pub data_rx_kb: u32,
+	/// Largest packet size, in bytes
+	pub packet_max: u32,
+	/// Average packet size, in bytes
+	pub packet_avg: u32,
+}
+
+/// Parse a `%XCONNSTAT` response, e.g. `%XCONNSTAT: 0,0,12,34,708,150`.
+pub fn parse_xconnstat(response: &str) -> Option<ConnStats> {
+	let value = extract_value(response, "%XCONNSTAT:")?;
+	let mut fields = value.split(',').map(|field| field.trim().parse::<u32>());
+	let mut next = || fields.next()?.ok();
+	Some(ConnStats {
+		sms_tx: next()?,
+		sms_rx: next()?,
+		data_tx_kb: next()?,
+		data_rx_kb: next()?,
+		packet_max: next()?,
+		packet_avg: next()?,
+	})
+}
+
+/// Start collecting connection statistics (`AT%XCONNSTAT=1`).
+///
+/// Collection must be running for `get_conn_stats` to report data.
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::AtCommand)` on failure.
+pub async fn start_conn_stats<'a>(control: &Control<'a>) -> Result<()> {
+	at_command_ok(control, "AT%XCONNSTAT=1").await
+}
+
+/// Reset the connection statistics and keep collecting.
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::AtCommand)` on failure.
+pub async fn reset_conn_stats<'a>(control: &Control<'a>) -> Result<()> {
+	at_command_ok(control, "AT%XCONNSTAT=0").await?;
+	start_conn_stats(control).await
+}
+
+/// Get the connection statistics (`AT%XCONNSTAT?`).
+///
+/// # Returns
+/// `Ok(stats)` on success, `Err(Error::InvalidResponse)` if the response is
+/// malformed, e.g. because collection was never started.
+pub async fn get_conn_stats<'a>(control: &Control<'a>) -> Result<ConnStats> {
+	let mut resp_buf = [0u8; 96];
+	let len = at_command(control, "AT%XCONNSTAT?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	parse_xconnstat(resp).ok_or(Error::InvalidResponse)
+}
+
+/// eDRX access technology (`<AcT-type>` of `AT+CEDRXS`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdrxAct {
+	/// LTE-M (E-UTRAN WB-S1 mode)
+	LteM = 4,
+	/// NB-IoT (E-UTRAN NB-S1 mode)
+	NbIot = 5,
+}
+
+/// Requested eDRX settings, as 4-bit values of 3GPP TS 24.008 (10.5.5.32).
+///
+/// The `cycle` selects the eDRX cycle length (e.g. `0b0101` is 81.92 s).
+/// The paging time window `ptw` is how long the modem listens for paging
+/// at the start of each cycle: `(ptw + 1) * 1.28 s` on LTE-M, `(ptw + 1) *
+/// 2.56 s` on NB-IoT. A longer window lets downlink data through sooner
+/// after the cycle starts, at the cost of current.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EdrxConfig {
+	/// eDRX cycle value, 0 to 15
+	pub cycle: u8,
+	/// Paging time window value, 0 to 15
+	pub ptw: u8,
+}
+
+impl EdrxConfig {
+	/// Pack into the eDRX parameters octet: PTW in the upper nibble, the
+	/// cycle in the lower.
+	pub const fn to_octet(self) -> u8 {
+		((self.ptw & 0x0f) << 4) | (self.cycle & 0x0f)
+	}
+
+	/// Unpack an eDRX parameters octet, see `to_octet`.
+	pub const fn from_octet(octet: u8) -> Self {
+		Self {
+			cycle: octet & 0x0f,
+			ptw: octet >> 4,
+		}
+	}
+}
+
+/// Request eDRX with the given cycle and paging time window.
+///
+/// The nRF91 does not take the packed octet (see `EdrxConfig::to_octet`):
+/// `AT+CEDRXS` only accepts the 4-bit cycle, and the paging time window is
+/// requested separately with `AT%XPTW`. Both apply from the next attach or
+/// tracking area update; the network may grant different values.
+///
+/// # Errors
+/// Returns `Error::Config` if a value does not fit in 4 bits, or
+/// `Error::AtCommand` if the modem rejects a command.
+pub async fn set_edrx<'a>(control: &Control<'a>, act: EdrxAct, config: EdrxConfig) -> Result<()> {
+	if config.cycle > 0x0f || config.ptw > 0x0f {
+		return Err(Error::Config);
+	}
+
+	let mut cmd: String<32> = String::new();
+	let _ = write!(cmd, "AT+CEDRXS=2,{},\"{:04b}\"", act as u8, config.cycle);
+	at_command_ok(control, &cmd).await?;
+
+	cmd.clear();
+	let _ = write!(cmd, "AT%XPTW={},\"{:04b}\"", act as u8, config.ptw);
+	at_command_ok(control, &cmd).await
+}
+
+/// Disable eDRX (`AT+CEDRXS=0`).
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::AtCommand)` on failure.
+pub async fn disable_edrx<'a>(control: &Control<'a>) -> Result<()> {
+	at_command_ok(control, "AT+CEDRXS=0").await
+}
+
+/// RRC connection state (`AT+CSCON`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RrcState {
+	/// No radio connection; the modem can drop to low power
+	Idle,
+	/// Radio connection up; the modem stays in a high-power state
+	Connected,
+}
+
+/// Interval between RRC state queries in `await_rrc_idle`.
+const RRC_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Parse a `+CSCON` response, e.g. `+CSCON: 0,1`.
+pub fn parse_cscon(response: &str) -> Option<RrcState> {
+	let value = extract_value(response, "+CSCON:")?;
+	match value.split(',').nth(1)?.trim() {
+		"0" => Some(RrcState::Idle),
+		"1" => Some(RrcState::Connected),
+		_ => None,
+	}
+}
+
+/// Get the RRC connection state (`AT+CSCON?`).
+///
+/// # Returns
+/// `Ok(state)` on success, `Err(Error::InvalidResponse)` if the response is
+/// malformed.
+pub async fn get_rrc_state<'a>(control: &Control<'a>) -> Result<RrcState> {
+	let mut resp_buf = [0u8; 64];
+	let len = at_command(control, "AT+CSCON?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	parse_cscon(resp).ok_or(Error::InvalidResponse)
+}
+
+/// Wait until the modem has released the RRC connection.
+///
+/// Call this before putting the MCU into deep sleep: while the connection
+/// is up the modem stays in a high-power state, and sleeping the MCU does
+/// not change that. The network releases the connection after its
+/// inactivity timer, typically 10 to 60 seconds after the last data.
+///
+/// # Returns
+/// `Ok(())` once the modem reports idle.
+///
+/// # Errors
+/// Returns `Error::Timeout` if the connection is still up after `timeout`,
+/// or `Error::InvalidResponse` if the state cannot be read.
+pub async fn await_rrc_idle<'a>(control: &Control<'a>, timeout: Duration) -> Result<()> {
+	let idle = async {
+		loop {
+			if get_rrc_state(control).await? == RrcState::Idle {
+				return Ok(());
+			}
+			Timer::after(RRC_POLL_INTERVAL).await;
+		}
+	};
+
+	with_timeout(timeout, idle)
+		.await
+		.map_err(|_| Error::Timeout)?
+}
+
+/// Highest LTE band number accepted by `%XBANDLOCK`.
+pub const BAND_MAX: u8 = 88;
+
+/// Apply a setting that only takes effect across a functional mode cycle.
+///
+/// Takes the modem to CFUN=0, sends `set_cmd`, returns to the previous
+/// functional mode and reads the setting back with `query_cmd`. The value
+/// following `prefix` in the query response is passed to `verify`.
+///
+/// CFUN=4 is restored as is; any other active mode comes back as CFUN=1,
+/// so registration has to be awaited again afterwards.
+///
+/// # Returns
+/// `Ok(())` if the read-back value was accepted by `verify`.
+///
+/// # Errors
+/// Returns `Error::AtCommand` if the modem rejects `set_cmd` or a CFUN
+/// change, and `Error::Config` if the setting did not stick.
+pub async fn apply_and_verify<'a>(
+	control: &Control<'a>,
+	set_cmd: &str,
+	query_cmd: &str,
+	prefix: &str,
+	verify: impl Fn(&str) -> bool,
+) -> Result<()> {
+	let mode = get_functional_mode(control).await?;
+	disable(control).await?;
+
+	let applied = at_command_ok(control, set_cmd).await;
+
+	// Restore the previous mode even if the setting was rejected
+	match mode {
+		0 => {}
+		4 => at_command_ok_timeout(control, "AT+CFUN=4", AT_TIMEOUT).await?,
+		_ => enable(control).await?,
+	}
+	applied?;
+
+	let mut buf = [0u8; 256];
+	match query_value(control, query_cmd, prefix, &mut buf).await {
+		Some(value) if verify(value) => Ok(()),
+		_ => Err(Error::Config),
+	}
+}
+
+/// Set the enabled system modes and verify they were applied.
+///
+/// # Arguments
+/// * `lte_m` - Enable LTE-M
+/// * `nb_iot` - Enable NB-IoT
+/// * `gnss` - Enable GNSS
+/// * `preference` - `%XSYSTEMMODE` preference (0 = no preference)
+///
+/// # Errors
+/// See `apply_and_verify`.
+pub async fn set_system_mode<'a>(
+	control: &Control<'a>,
+	lte_m: bool,
+	nb_iot: bool,
+	gnss: bool,
+	preference: u8,
+) -> Result<()> {
+	let mut value: String<16> = String::new();
+	let _ = write!(
+		value,
+		"{},{},{},{}",
+		u8::from(lte_m),
+		u8::from(nb_iot),
+		u8::from(gnss),
+		preference
+	);
+	let mut cmd: String<32> = String::new();
+	let _ = write!(cmd, "AT%XSYSTEMMODE={}", value);
+
+	apply_and_verify(control, &cmd, "AT%XSYSTEMMODE?", "%XSYSTEMMODE:", |read| {
+		read == value.as_str()
+	})
+	.await
+}
+
+/// Parse a `%XCBAND=?` response, e.g. `%XCBAND: (1,2,3,4,5,8,12,13,20,28)`.
+///
+/// Band numbers that are malformed or outside `1..=BAND_MAX` are skipped.
+pub fn parse_xcband_support(response: &str) -> Vec<u8, { BAND_MAX as usize }> {
+	let Some(value) = extract_value(response, "%XCBAND:") else {
+		return Vec::new();
+	};
+	value.trim_matches(|c| matches!(c, '(' | ')'))
+		.split(',')
+		.filter_map(|band| band.trim().parse().ok())
+		.filter(|band| (1..=BAND_MAX).contains(band))
+		.take(BAND_MAX as usize)
+		.collect()
+}
+
+/// Get the bands supported by the modem firmware (`AT%XCBAND=?`).
+///
+/// # Returns
+/// The supported band numbers.
+///
+/// # Errors
+/// Returns `Error::InvalidResponse` if the response holds no bands.
+pub async fn get_supported_bands<'a>(
+	control: &Control<'a>,
+) -> Result<Vec<u8, { BAND_MAX as usize }>> {
+	let mut resp_buf = [0u8; 256];
+	let len = at_command(control, "AT%XCBAND=?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+	let bands = parse_xcband_support(resp);
+	if bands.is_empty() {
+		return Err(Error::InvalidResponse);
+	}
+	Ok(bands)
+}
+
+/// Permanently lock the modem to `bands` and verify the lock was applied.
+///
+/// `bands` are checked against `get_supported_bands` first, since a lock to
+/// only unsupported bands leaves the modem unable to find any cell. An
+/// empty `bands` removes the lock.
+///
+/// # Errors
+/// Returns `Error::Config` for a band the modem does not support,
+/// `Error::InvalidResponse` if the supported bands cannot be read,
+/// otherwise see `apply_and_verify`.
+pub async fn set_band_lock<'a>(control: &Control<'a>, bands: &[u8]) -> Result<()> {
+	let supported = if bands.is_empty() {
+		Vec::new()
+	} else {
+		get_supported_bands(control).await?
+	};
+
+	// Bit mask as a string of '0'/'1', band 1 being the rightmost bit
+	let mut mask = [b'0'; BAND_MAX as usize];
+	for &band in bands {
+		if !supported.contains(&band) {
+			return Err(Error::Config);
+		}
+		mask[(BAND_MAX - band) as usize] = b'1';
+	}
+	// Only ASCII digits were written
+	let mask = core::str::from_utf8(&mask).map_err(|_| Error::Config)?;
+
+	let cmd = AtCommandBuilder::<128>::new("AT%XBANDLOCK")?;
+	let cmd = if bands.is_empty() {
+		cmd.num(0u8)?
 	} else {
-		None
+		cmd.num(1u8)?.string(mask)?
+	};
+
+	apply_and_verify(
+		control,
+		cmd.as_str(),
+		"AT%XBANDLOCK?",
+		"%XBANDLOCK:",
+		|read| {
+			// First value is the permanent lock
+			let permanent = read.split(',').next().unwrap_or("").trim_matches('"');
+			if bands.is_empty() {
+				!permanent.contains('1')
+			} else {
+				permanent == mask
+			}
+		},
+	)
+	.await
+}
+
+/// Device identification, for inventory and provisioning.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+	/// Manufacturer (`AT+CGMI`)
+	pub manufacturer: String<32>,
+	/// Model (`AT+CGMM`)
+	pub model: String<32>,
+	/// Modem firmware version (`AT+CGMR`)
+	pub firmware_version: String<32>,
+	/// IMEI (`AT+CGSN`)
+	pub imei: String<16>,
+	/// SIM ICCID, `None` if no SIM is inserted
+	pub iccid: Option<String<24>>,
+	/// SIM IMSI, `None` if no SIM is inserted or it is locked
+	pub imsi: Option<String<16>>,
+}
+
+/// Read all device identification values in one go.
+///
+/// # Returns
+/// The device info, or `Err(Error::InvalidResponse)` if any value other
+/// than the ICCID and IMSI is missing or too long.
+pub async fn get_device_info(control: &Control<'_>) -> Result<DeviceInfo> {
+	fn owned<const N: usize>(value: Option<&str>) -> Result<String<N>> {
+		String::try_from(value.ok_or(Error::InvalidResponse)?)
+			.map_err(|_| Error::InvalidResponse)
+	}
+
+	let mut buf = [0u8; 128];
+	let manufacturer = owned(get_manufacturer(control, &mut buf).await)?;
+	let model = owned(get_model(control, &mut buf).await)?;
+	let firmware_version = owned(get_firmware_version(control, &mut buf).await)?;
+	let imei = owned(get_imei(control, &mut buf).await)?;
+	let iccid = owned(get_iccid(control, &mut buf).await).ok();
+	let imsi = owned(get_imsi(control, &mut buf).await).ok();
+
+	Ok(DeviceInfo {
+		manufacturer,
+		model,
+		firmware_version,
+		imei,
+		iccid,
+		imsi,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn xmonitor_registered() {
+		let resp = "%XMONITOR: 1,\"Telia N\",\"Telia N\",\"24202\",\"0901\",7,20,\"012BEEF3\",\
+			428,6400,53,35,\"\",\"00100001\",\"00000110\",\"01001001\"\r\nOK\r\n";
+		let status = parse_xmonitor(resp).unwrap();
+
+		assert_eq!(status.registration, RegistrationStatus::RegisteredHome);
+		assert_eq!(status.full_name.as_deref(), Some("Telia N"));
+		assert_eq!(status.short_name.as_deref(), Some("Telia N"));
+		assert_eq!(status.plmn.as_deref(), Some("24202"));
+		assert_eq!(status.tac, Some(0x0901));
+		assert_eq!(status.act, Some(7));
+		assert_eq!(status.band, Some(20));
+		assert_eq!(status.cell_id, Some(0x012B_EEF3));
+		assert_eq!(status.phys_cell_id, Some(428));
+		assert_eq!(status.earfcn, Some(6400));
+		assert_eq!(status.rsrp_dbm, Some(-87));
+		assert_eq!(status.snr_db, Some(11));
+		assert_eq!(status.edrx, None);
+		assert_eq!(status.active_time, Some(Duration::from_secs(60)));
+		assert_eq!(status.periodic_tau, Some(Duration::from_secs(60 * 60)));
+	}
+
+	#[test]
+	fn xmonitor_not_registered() {
+		let status = parse_xmonitor("%XMONITOR: 2\r\nOK\r\n").unwrap();
+
+		assert_eq!(status.registration, RegistrationStatus::Searching);
+		assert_eq!(status.full_name, None);
+		assert_eq!(status.plmn, None);
+		assert_eq!(status.cell_id, None);
+		assert_eq!(status.rsrp_dbm, None);
+		assert_eq!(status.active_time, None);
+	}
+
+	#[test]
+	fn xmonitor_unknown_signal() {
+		let resp = "%XMONITOR: 1,\"\",\"\",\"24202\",\"0901\",9,20,\"012BEEF3\",\
+			428,6400,255,127,\"\",\"11100000\",\"11100000\",\"00001001\"\r\nOK\r\n";
+		let status = parse_xmonitor(resp).unwrap();
+
+		assert_eq!(status.full_name, None);
+		assert_eq!(status.plmn.as_deref(), Some("24202"));
+		assert_eq!(status.rsrp_dbm, None);
+		assert_eq!(status.snr_db, None);
+		assert_eq!(status.active_time, None);
+		assert_eq!(status.periodic_tau, None);
+	}
+
+	#[test]
+	fn xmonitor_names_with_commas() {
+		let resp = "%XMONITOR: 5,\"Operator, Inc.\",\"Op,X\",\"310410\",\"2B11\",7,12,\"0A1B2C3D\",\
+			71,5110,60,30,\"0010\",\"00000011\",\"00100001\",\"01001001\"\r\nOK\r\n";
+		let status = parse_xmonitor(resp).unwrap();
+
+		assert_eq!(status.registration, RegistrationStatus::RegisteredRoaming);
+		assert_eq!(status.full_name.as_deref(), Some("Operator, Inc."));
+		assert_eq!(status.short_name.as_deref(), Some("Op,X"));
+		assert_eq!(status.plmn.as_deref(), Some("310410"));
+		assert_eq!(status.band, Some(12));
+		assert_eq!(status.edrx, Some(0b0010));
+		assert_eq!(status.active_time, Some(Duration::from_secs(6)));
+	}
+
+	#[test]
+	fn split_fields_keeps_quoted_commas() {
+		let fields: [&str; 4] = {
+			let mut it = split_fields("1, \"a,b\",,\"c\"");
+			core::array::from_fn(|_| it.next().unwrap())
+		};
+		assert_eq!(fields, ["1", "\"a,b\"", "", "\"c\""]);
+	}
+
+	#[test]
+	fn edrx_octet() {
+		let config = EdrxConfig { cycle: 5, ptw: 3 };
+		assert_eq!(config.to_octet(), 0x35);
+		assert_eq!(EdrxConfig::from_octet(0x35), config);
+		assert_eq!(EdrxConfig::from_octet(config.to_octet()), config);
+
+		// Bits above the nibble are dropped
+		assert_eq!(
+			EdrxConfig {
+				cycle: 0x15,
+				ptw: 0x23
+			}
+			.to_octet(),
+			0x35
+		);
+		assert_eq!(
+			EdrxConfig::from_octet(0xf0),
+			EdrxConfig { cycle: 0, ptw: 15 }
+		);
 	}
 }
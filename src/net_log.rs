@@ -0,0 +1,156 @@
+//! Log mirroring to a remote TCP collector.
+//!
+//! Compiled in with the `net-log` feature. Once `start()` has been called,
+//! every line written through a `Logger` is also queued here, and
+//! `net_log_task` streams the queue to the collector as plain text, one
+//! line per `\r\n`. Any TCP listener works as a collector, e.g.
+//! `nc -lk 5140`.
+//!
+//! ## Never Blocking
+//! Queuing uses `try_send` on a bounded channel of `QUEUE_LEN` lines, so
+//! logging never waits for the network. While the collector is unreachable
+//! (no network, connection lost) lines pile up until the queue is full and
+//! are then dropped and counted. After reconnecting, the number of lines
+//! dropped since the last connection is reported first. Lines longer than
+//! `LINE_MAX` are truncated.
+//!
+//! ## Error Handling
+//! `start()` returns `Error::TaskSpawn` if the task cannot be spawned.
+//! Connection failures are retried with backoff and never surface.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+
+use core::fmt::Write as _;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use embassy_executor::Spawner;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use heapless::String;
+
+use crate::backoff::Backoff;
+use crate::network;
+
+/// Longest line mirrored; longer lines are truncated.
+pub const LINE_MAX: usize = 256;
+
+/// Number of lines buffered while the collector is unreachable.
+pub const QUEUE_LEN: usize = 16;
+
+/// Size of the socket RX and TX buffers.
+const SOCKET_BUF_SIZE: usize = 512;
+
+/// Inactivity timeout for the connection.
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Lines waiting to be sent.
+static LINES: Channel<CriticalSectionRawMutex, String<LINE_MAX>, QUEUE_LEN> = Channel::new();
+
+/// Lines dropped since the last report to the collector.
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// Lines dropped since boot.
+static DROPPED_TOTAL: AtomicU32 = AtomicU32::new(0);
+
+// Set once the task runs; lines are not queued before
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Queue a log line (without line ending) for the collector.
+///
+/// Does nothing until `start()` has been called. Never blocks: if the
+/// queue is full the line is dropped and counted.
+pub fn push(line: &str) {
+	if !RUNNING.load(Ordering::Acquire) {
+		return;
+	}
+
+	let mut owned: String<LINE_MAX> = String::new();
+	for c in line.chars() {
+		if owned.push(c).is_err() {
+			break;
+		}
+	}
+	if LINES.try_send(owned).is_err() {
+		DROPPED.fetch_add(1, Ordering::Relaxed);
+		DROPPED_TOTAL.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+/// Number of lines dropped since boot because the queue was full.
+pub fn dropped() -> u32 {
+	DROPPED_TOTAL.load(Ordering::Relaxed)
+}
+
+/// Task streaming queued log lines to the collector at `host`:`port`.
+///
+/// Reconnects with backoff whenever resolving, connecting or writing
+/// fails. A line being written when the connection fails is lost.
+#[embassy_executor::task]
+pub async fn net_log_task(stack: &'static Stack<'static>, host: &'static str, port: u16) -> ! {
+	let mut backoff = Backoff::new(Duration::from_secs(5), Duration::from_secs(300));
+	let mut rx_buffer = [0u8; SOCKET_BUF_SIZE];
+	let mut tx_buffer = [0u8; SOCKET_BUF_SIZE];
+
+	loop {
+		let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+		socket.set_timeout(Some(SOCKET_TIMEOUT));
+
+		let connected = match network::resolve(stack, host).await {
+			Ok(address) => {
+				network::tcp_connect(&mut socket, IpEndpoint::new(address, port))
+					.await
+					.is_ok()
+			}
+			Err(_) => false,
+		};
+		if connected {
+			backoff.reset();
+			let _ = stream_lines(&mut socket).await;
+		}
+
+		socket.abort();
+		let _ = socket.flush().await;
+		drop(socket);
+		Timer::after(backoff.next_delay()).await;
+	}
+}
+
+/// Report dropped lines, then send queued lines until a write fails.
+async fn stream_lines(socket: &mut TcpSocket<'_>) -> Result<()> {
+	let dropped = DROPPED.swap(0, Ordering::Relaxed);
+	if dropped > 0 {
+		let mut notice: String<48> = String::new();
+		let _ = write!(notice, "[net_log] {} lines dropped\r\n", dropped);
+		network::tcp_write_all(socket, notice.as_bytes()).await?;
+	}
+
+	loop {
+		let line = LINES.receive().await;
+		network::tcp_write_all(socket, line.as_bytes()).await?;
+		network::tcp_write_all(socket, b"\r\n").await?;
+	}
+}
+
+/// Start mirroring log lines to the collector at `host`:`port`.
+///
+/// Call once the network is up; the task keeps reconnecting on its own
+/// afterwards. Lines logged before this call are not mirrored.
+///
+/// # Errors
+/// Returns `Error::TaskSpawn` if the task cannot be spawned.
+pub fn start(
+	spawner: &Spawner,
+	stack: &'static Stack<'static>,
+	host: &'static str,
+	port: u16,
+) -> Result<()> {
+	let token = net_log_task(stack, host, port).map_err(|_| Error::TaskSpawn)?;
+	spawner.spawn(token);
+	RUNNING.store(true, Ordering::Release);
+	Ok(())
+}
@@ -3,23 +3,102 @@
 //! This module provides TCP/IP networking over the cellular modem
 //! using the embassy-net stack with embassy-net-nrf91 driver.
 //!
+//! ## Inbound Connections
+//! `listen()` accepts TCP connections, but most operator APNs put the
+//! device behind carrier-grade NAT with no inbound route. Listening is only
+//! useful on private APNs where the device address is reachable, and the
+//! address may change on every PDP activation.
+//!
 //! ## Error Handling
 //! Functions return `Result<T, Error>` where errors should be handled
 //! by the caller. For fatal errors, use the `fatal_error!` macro.
 
 #![allow(dead_code)]
 
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, SocketError};
+
+use core::cell::RefCell;
 
 use embassy_executor::Spawner;
-use embassy_net::{ConfigV4, Ipv4Address, Ipv4Cidr, Stack, StackResources, StaticConfigV4};
+use embassy_net::dns::DnsQueryType;
+use embassy_net::tcp::{State, TcpSocket};
+use embassy_net::udp::UdpSocket;
+use embassy_net::{
+	ConfigV4, ConfigV6, IpAddress, IpEndpoint, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr,
+	Stack, StackResources, StaticConfigV4, StaticConfigV6,
+};
 use embassy_net_nrf91::NetDriver;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{with_deadline, Duration, Instant};
+use heapless::{FnvIndexMap, String, Vec};
 use static_cell::StaticCell;
 
+use crate::budget;
+
 /// Network stack resources.
 /// Adjust socket count based on application needs.
 const SOCKET_COUNT: usize = 4;
 
+/// Per-connection buffer size for `listen()`.
+const LISTEN_BUF_SIZE: usize = 1024;
+
+/// Idle timeout for accepted connections.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connect timeout for `connectivity_check()`.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of host names kept in the DNS cache (must be a power of two).
+const DNS_CACHE_SIZE: usize = 8;
+
+/// Maximum length of a cached host name.
+const DNS_HOST_MAX: usize = 64;
+
+/// Lifetime of a cached DNS entry.
+///
+/// embassy-net does not expose the record TTL, so a fixed lifetime is used.
+const DNS_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Cached DNS answer.
+#[derive(Clone, Copy)]
+struct DnsEntry {
+	address: IpAddress,
+	expires: Instant,
+}
+
+type DnsCache = FnvIndexMap<String<DNS_HOST_MAX>, DnsEntry, DNS_CACHE_SIZE>;
+
+/// Host name to address cache, saving a radio round-trip per repeated lookup.
+static DNS_CACHE: Mutex<CriticalSectionRawMutex, RefCell<DnsCache>> =
+	Mutex::new(RefCell::new(FnvIndexMap::new()));
+
+/// Maximum number of DNS servers in the stack configuration.
+pub const DNS_SERVERS_MAX: usize = 3;
+
+/// Fixed local ports bound with `udp_bind`, until released by `udp_close`.
+static FIXED_PORTS: Mutex<CriticalSectionRawMutex, RefCell<Vec<u16, SOCKET_COUNT>>> =
+	Mutex::new(RefCell::new(Vec::new()));
+
+/// DNS servers set by the application, replacing the network-provided ones.
+static DNS_OVERRIDE: Mutex<CriticalSectionRawMutex, RefCell<Vec<Ipv4Address, DNS_SERVERS_MAX>>> =
+	Mutex::new(RefCell::new(Vec::new()));
+
+/// DNS servers last provided by the network, used when there is no
+/// override.
+static NETWORK_DNS: Mutex<CriticalSectionRawMutex, RefCell<Vec<Ipv4Address, DNS_SERVERS_MAX>>> =
+	Mutex::new(RefCell::new(Vec::new()));
+
+/// Get the DNS servers to configure: the override if set, otherwise the
+/// network-provided ones.
+fn active_dns_servers() -> Vec<Ipv4Address, DNS_SERVERS_MAX> {
+	let servers = DNS_OVERRIDE.lock(|servers| servers.borrow().clone());
+	if !servers.is_empty() {
+		return servers;
+	}
+	NETWORK_DNS.lock(|servers| servers.borrow().clone())
+}
+
 /// Task to run the embassy-net stack.
 ///
 /// This task handles IP packet processing and must run continuously.
@@ -37,6 +116,8 @@ pub async fn net_task(mut runner: embassy_net::Runner<'static, NetDriver<'static
 /// # Arguments
 /// * `spawner` - Embassy spawner for task creation
 /// * `device` - The nRF91 modem NetDriver from embassy-net-nrf91
+/// * `seed` - Random seed for the stack (TCP initial sequence numbers,
+///   ephemeral ports). See `seed_from_imei`.
 ///
 /// # Returns
 /// `Ok(&Stack)` on success, `Err(Error)` on failure
@@ -46,6 +127,7 @@ pub async fn net_task(mut runner: embassy_net::Runner<'static, NetDriver<'static
 pub async fn init(
 	spawner: &Spawner,
 	device: NetDriver<'static>,
+	seed: u64,
 ) -> Result<&'static Stack<'static>> {
 	// Network stack resources (sockets, etc.)
 	static RESOURCES: StaticCell<StackResources<SOCKET_COUNT>> = StaticCell::new();
@@ -55,8 +137,6 @@ pub async fn init(
 	// IP configuration will be set when PDP context is activated
 	let config = embassy_net::Config::default();
 
-	let seed = embassy_time::Instant::now().as_ticks();
-
 	static STACK: StaticCell<Stack<'static>> = StaticCell::new();
 	let (stack, runner) = embassy_net::new(device, config, resources, seed);
 	let stack = STACK.init(stack);
@@ -68,18 +148,146 @@ pub async fn init(
 	Ok(stack)
 }
 
+/// Derive a stack seed from the device IMEI and the current time.
+///
+/// The boot time alone is nearly identical on every boot, which makes TCP
+/// initial sequence numbers predictable and can collide with a previous
+/// connection to the same peer. Mixing in the IMEI makes the seed unique
+/// per device. The nRF91 application core has no hardware RNG peripheral.
+pub fn seed_from_imei(imei: &str) -> u64 {
+	// FNV-1a over the IMEI, then splitmix64 finalization with the time
+	let hash = imei.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+		(hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+	});
+
+	let mut z = hash ^ Instant::now()
+		.as_ticks()
+		.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+	z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	z ^ (z >> 31)
+}
+
+/// Maximum length of a dotted-quad IPv4 address (`255.255.255.255`).
+pub const IPV4_STR_MAX: usize = 15;
+
+/// Format raw IPv4 octets as a dotted-quad string.
+///
+/// Writes digits directly instead of going through `core::fmt`, so it can
+/// be used wherever only the octets are at hand (e.g. from our own AT
+/// response parsers).
+///
+/// # Errors
+/// Returns `Err(())` if `buf` runs out of capacity; `buf` then holds a
+/// truncated address.
+pub fn fmt_ipv4<const N: usize>(buf: &mut String<N>, ip: [u8; 4]) -> core::result::Result<(), ()> {
+	for (i, octet) in ip.iter().enumerate() {
+		if i > 0 {
+			buf.push('.')?;
+		}
+		let hundreds = octet / 100;
+		let tens = octet / 10 % 10;
+		if hundreds > 0 {
+			buf.push(char::from(b'0' + hundreds))?;
+		}
+		if hundreds > 0 || tens > 0 {
+			buf.push(char::from(b'0' + tens))?;
+		}
+		buf.push(char::from(b'0' + octet % 10))?;
+	}
+	Ok(())
+}
+
+/// Format an IPv4 address for logging, see `fmt_ipv4`.
+pub fn ipv4_str(ip: Ipv4Address) -> String<IPV4_STR_MAX> {
+	let mut buf = String::new();
+	// Any address fits in IPV4_STR_MAX
+	let _ = fmt_ipv4(&mut buf, ip.octets());
+	buf
+}
+
 /// Set the IPv4 configuration on the stack.
 ///
 /// Call this when the modem provides IP configuration from PDP context.
+/// DNS servers set with `set_dns_servers` are applied.
 pub fn set_ipv4_config(stack: &Stack<'_>, address: Ipv4Address, gateway: Option<Ipv4Address>) {
+	set_ipv4_config_with(stack, address, 24, gateway, &[]); // Typical cellular prefix
+}
+
+/// Set the IPv4 configuration on the stack, including network-provided
+/// DNS servers.
+///
+/// DNS servers set with `set_dns_servers` take precedence over `dns`.
+pub fn set_ipv4_config_with(
+	stack: &Stack<'_>,
+	address: Ipv4Address,
+	prefix_len: u8,
+	gateway: Option<Ipv4Address>,
+	dns: &[Ipv4Address],
+) {
+	let network_dns = dns.iter().copied().take(DNS_SERVERS_MAX).collect();
+	NETWORK_DNS.lock(|stored| *stored.borrow_mut() = network_dns);
+
 	let static_config = StaticConfigV4 {
-		address: Ipv4Cidr::new(address, 24), // Typical cellular prefix
+		address: Ipv4Cidr::new(address, prefix_len),
 		gateway,
-		dns_servers: Default::default(),
+		dns_servers: active_dns_servers(),
 	};
 	stack.set_config_v4(ConfigV4::Static(static_config));
 }
 
+/// Set the IPv6 configuration on the stack, including network-provided
+/// DNS servers.
+///
+/// Needed for names that only resolve over IPv6 on IPv6 and dual-stack
+/// contexts. The IPv4 override from `set_dns_servers` does not apply.
+pub fn set_ipv6_config_with(
+	stack: &Stack<'_>,
+	address: Ipv6Address,
+	prefix_len: u8,
+	gateway: Option<Ipv6Address>,
+	dns: &[Ipv6Address],
+) {
+	let static_config = StaticConfigV6 {
+		address: Ipv6Cidr::new(address, prefix_len),
+		gateway,
+		dns_servers: dns.iter().copied().take(DNS_SERVERS_MAX).collect(),
+	};
+	stack.set_config_v6(ConfigV6::Static(static_config));
+}
+
+/// Override the DNS servers used by the stack.
+///
+/// Takes precedence over the servers provided by the network, for carriers
+/// whose resolvers are unreliable. Applied to the current configuration
+/// right away and kept across later `set_ipv4_config` calls. Pass an empty
+/// slice to remove the override and go back to the servers last provided
+/// by the network. At most `DNS_SERVERS_MAX` servers are used.
+pub fn set_dns_servers(stack: &Stack<'_>, servers: &[Ipv4Address]) {
+	let servers = servers.iter().copied().take(DNS_SERVERS_MAX).collect();
+	DNS_OVERRIDE.lock(|stored| *stored.borrow_mut() = servers);
+
+	if let Some(mut config) = stack.config_v4() {
+		config.dns_servers = active_dns_servers();
+		stack.set_config_v4(ConfigV4::Static(config));
+	}
+	dns_cache_clear();
+}
+
+/// Set the DNS servers of the current configuration only.
+///
+/// Unlike `set_dns_servers`, nothing is kept: the next `set_ipv4_config`
+/// call applies the network-provided servers (or the override) again. Use
+/// this for stopgaps such as a public resolver while the network provides
+/// none. Does nothing without an IPv4 configuration.
+pub fn set_current_dns_servers(stack: &Stack<'_>, servers: &[Ipv4Address]) {
+	if let Some(mut config) = stack.config_v4() {
+		config.dns_servers = servers.iter().copied().take(DNS_SERVERS_MAX).collect();
+		stack.set_config_v4(ConfigV4::Static(config));
+		dns_cache_clear();
+	}
+}
+
 /// Wait for the network stack to have a valid IP configuration.
 ///
 /// This waits until the modem provides an IP address through PDP context.
@@ -106,3 +314,613 @@ pub async fn wait_for_link(stack: &Stack<'_>) {
 pub fn get_ipv4_config(stack: &Stack<'_>) -> Option<StaticConfigV4> {
 	stack.config_v4()
 }
+
+/// Look up the IPv4 address of `host`, bypassing the DNS cache.
+async fn query_a(stack: &Stack<'_>, host: &str) -> Result<IpAddress> {
+	let addresses = stack
+		.dns_query(host, DnsQueryType::A)
+		.await
+		.map_err(|_| Error::InvalidResponse)?;
+	addresses.first().copied().ok_or(Error::InvalidResponse)
+}
+
+/// Resolve a host name to an IPv4 address, using the DNS cache.
+///
+/// Cached answers are reused until they expire, so repeated lookups of the
+/// same SMTP/IMAP host do not cost a radio round-trip. Requires DNS servers
+/// in the stack configuration; `dns::DnsClient` queries a given resolver
+/// instead.
+///
+/// # Returns
+/// The resolved address, or `Err(Error::InvalidResponse)` if the lookup
+/// failed or returned no address.
+pub async fn resolve(stack: &Stack<'_>, host: &str) -> Result<IpAddress> {
+	let now = Instant::now();
+	let key: Option<String<DNS_HOST_MAX>> = String::try_from(host).ok();
+
+	if let Some(key) = &key {
+		let cached = DNS_CACHE.lock(|cache| cache.borrow().get(key).copied());
+		if let Some(entry) = cached.filter(|entry| entry.expires > now) {
+			return Ok(entry.address);
+		}
+	}
+
+	let address = query_a(stack, host).await?;
+
+	// Host names too long for the cache are resolved but not cached
+	if let Some(key) = key {
+		let entry = DnsEntry {
+			address,
+			expires: now + DNS_CACHE_TTL,
+		};
+		DNS_CACHE.lock(|cache| insert_dns_entry(&mut cache.borrow_mut(), key, entry));
+	}
+
+	Ok(address)
+}
+
+/// Clear the DNS cache.
+///
+/// Called when the device address changes, since the resolver view may
+/// have changed with it.
+pub fn dns_cache_clear() {
+	DNS_CACHE.lock(|cache| cache.borrow_mut().clear());
+}
+
+/// Insert an entry, evicting the one closest to expiry if the cache is full.
+fn insert_dns_entry(cache: &mut DnsCache, key: String<DNS_HOST_MAX>, entry: DnsEntry) {
+	if cache.len() == cache.capacity() && !cache.contains_key(&key) {
+		let oldest = cache
+			.iter()
+			.min_by_key(|(_, entry)| entry.expires)
+			.map(|(host, _)| host.clone());
+		if let Some(oldest) = oldest {
+			cache.remove(&oldest);
+		}
+	}
+	let _ = cache.insert(key, entry);
+}
+
+/// Release a fixed port reserved by `udp_bind`.
+fn release_port(port: u16) {
+	FIXED_PORTS.lock(|ports| ports.borrow_mut().retain(|&bound| bound != port));
+}
+
+/// Local port of a socket bound with `udp_bind`.
+///
+/// Remembers whether the port was reserved as a fixed port, so that
+/// `udp_close` only releases reservations the socket holds.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use]
+pub struct UdpBinding {
+	port: u16,
+	fixed: bool,
+}
+
+impl UdpBinding {
+	/// Get the bound local port.
+	pub fn port(&self) -> u16 {
+		self.port
+	}
+}
+
+/// Bind a UDP socket to a local port.
+///
+/// Pass a fixed `local_port` for peers or firewalls that whitelist the
+/// source port (e.g. CoAP servers), or `None` for an ephemeral port.
+///
+/// The stack itself lets several sockets bind the same port, so fixed
+/// ports are tracked here. Close sockets with `udp_close`, passing the
+/// returned binding, to release the port.
+///
+/// # Returns
+/// The binding, holding the bound local port.
+///
+/// # Errors
+/// Returns `Error::Socket(SocketError::AddressInUse)` if another socket
+/// bound `local_port` through this function and has not been closed with
+/// `udp_close`, `Error::Socket(SocketError::TooManyPorts)` if
+/// `SOCKET_COUNT` fixed ports are already reserved, or
+/// `Error::Socket(SocketError::InvalidState)` if the socket is already
+/// bound.
+pub fn udp_bind(socket: &mut UdpSocket<'_>, local_port: Option<u16>) -> Result<UdpBinding> {
+	let Some(port) = local_port.filter(|&port| port != 0) else {
+		socket.bind(0)?;
+		return Ok(UdpBinding {
+			port: socket.endpoint().port,
+			fixed: false,
+		});
+	};
+
+	FIXED_PORTS.lock(|ports| {
+		let mut ports = ports.borrow_mut();
+		if ports.contains(&port) {
+			return Err(Error::Socket(SocketError::AddressInUse));
+		}
+		// At most one fixed port per socket, unless some were not released
+		ports.push(port)
+			.map_err(|_| Error::Socket(SocketError::TooManyPorts))
+	})?;
+
+	if let Err(e) = socket.bind(port) {
+		release_port(port);
+		return Err(e.into());
+	}
+	Ok(UdpBinding { port, fixed: true })
+}
+
+/// Close a UDP socket bound with `udp_bind`, releasing its fixed port.
+pub fn udp_close(socket: &mut UdpSocket<'_>, binding: UdpBinding) {
+	socket.close();
+	if binding.fixed {
+		release_port(binding.port);
+	}
+}
+
+/// Check that the data path works: resolve `host`, then open a TCP
+/// connection to it on `port`.
+///
+/// A successful DNS lookup is itself a sign the data path is healthy, and
+/// failing it points at a different layer than a failed connect. The
+/// lookup bypasses the DNS cache, so it always goes over the air.
+///
+/// # Returns
+/// The resolved endpoint, for reuse by the caller.
+///
+/// # Errors
+/// Returns `Error::InvalidResponse` if DNS resolution fails, or
+/// `Error::Socket` if the TCP connection fails.
+pub async fn connectivity_check(stack: &Stack<'_>, host: &str, port: u16) -> Result<IpEndpoint> {
+	let endpoint = IpEndpoint::new(query_a(stack, host).await?, port);
+
+	let mut rx_buffer = [0u8; 64];
+	let mut tx_buffer = [0u8; 64];
+	let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+	socket.set_timeout(Some(CHECK_TIMEOUT));
+
+	tcp_connect(&mut socket, endpoint).await?;
+	socket.close();
+	let _ = socket.flush().await;
+	Ok(endpoint)
+}
+
+/// TCP connection state, for diagnostics.
+///
+/// Mirrors the embassy-net socket state so callers do not depend on the
+/// stack's types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketState {
+	/// No connection
+	Closed,
+	/// Waiting for an inbound connection
+	Listen,
+	/// Connect sent, waiting for the peer's reply
+	SynSent,
+	/// Inbound connect received, handshake in progress
+	SynReceived,
+	/// Connection open
+	Established,
+	/// We closed, waiting for the peer's acknowledgement
+	FinWait1,
+	/// We closed and the peer acknowledged, waiting for the peer to close
+	FinWait2,
+	/// Peer closed, waiting for us to close
+	CloseWait,
+	/// Both sides closed at the same time
+	Closing,
+	/// Peer closed first, waiting for the acknowledgement of our close
+	LastAck,
+	/// Closed, waiting for stray segments to expire
+	TimeWait,
+}
+
+impl SocketState {
+	/// Get the conventional name of the state, e.g. `SYN-SENT`.
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Closed => "CLOSED",
+			Self::Listen => "LISTEN",
+			Self::SynSent => "SYN-SENT",
+			Self::SynReceived => "SYN-RECEIVED",
+			Self::Established => "ESTABLISHED",
+			Self::FinWait1 => "FIN-WAIT-1",
+			Self::FinWait2 => "FIN-WAIT-2",
+			Self::CloseWait => "CLOSE-WAIT",
+			Self::Closing => "CLOSING",
+			Self::LastAck => "LAST-ACK",
+			Self::TimeWait => "TIME-WAIT",
+		}
+	}
+}
+
+/// Get the state of a TCP socket.
+///
+/// A connection stuck in `SynSent` points at an unreachable peer, while
+/// `CloseWait` means the peer closed and we have not.
+pub fn socket_state(socket: &TcpSocket<'_>) -> SocketState {
+	match socket.state() {
+		State::Closed => SocketState::Closed,
+		State::Listen => SocketState::Listen,
+		State::SynSent => SocketState::SynSent,
+		State::SynReceived => SocketState::SynReceived,
+		State::Established => SocketState::Established,
+		State::FinWait1 => SocketState::FinWait1,
+		State::FinWait2 => SocketState::FinWait2,
+		State::CloseWait => SocketState::CloseWait,
+		State::Closing => SocketState::Closing,
+		State::LastAck => SocketState::LastAck,
+		State::TimeWait => SocketState::TimeWait,
+	}
+}
+
+/// Connect a TCP socket to a remote endpoint.
+///
+/// embassy-net always picks an ephemeral local port for outbound TCP
+/// connections; use `udp_bind` where a fixed source port is required.
+///
+/// # Errors
+/// Returns `Error::Config` if the data budget (see `budget`) is used up, or
+/// `Error::Socket` with the reason the connection failed.
+pub async fn tcp_connect(socket: &mut TcpSocket<'_>, remote: IpEndpoint) -> Result<()> {
+	budget::check()?;
+	socket.connect(remote).await?;
+	Ok(())
+}
+
+/// Enable or disable the TCP no-delay option (Nagle's algorithm off).
+///
+/// With Nagle enabled (the default), small writes are held back until
+/// earlier data is acknowledged so they can be coalesced into fewer
+/// segments. That saves radio time, and thus battery, on bulk transfers,
+/// but costs up to a round trip per command in request/response protocols
+/// such as SMTP. Set `no_delay` for those, and leave it off for bulk
+/// uploads.
+pub fn set_no_delay(socket: &mut TcpSocket<'_>, no_delay: bool) {
+	socket.set_nagle_enabled(!no_delay);
+}
+
+/// Write all of `data` to a TCP socket.
+///
+/// # Errors
+/// Returns `Error::Socket(SocketError::Closed)` if the peer closed the
+/// connection before everything was written, or the reset reason.
+pub async fn tcp_write_all(socket: &mut TcpSocket<'_>, data: &[u8]) -> Result<()> {
+	let mut written = 0;
+	while written < data.len() {
+		match socket.write(&data[written..]).await? {
+			0 => return Err(Error::Socket(SocketError::Closed)),
+			n => {
+				budget::record(n);
+				written += n;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Read from a TCP socket.
+///
+/// # Returns
+/// Number of bytes read; `0` means the peer closed the connection.
+///
+/// # Errors
+/// Returns `Error::Socket(SocketError::ConnectionReset)` if the connection
+/// was reset.
+pub async fn tcp_read(socket: &mut TcpSocket<'_>, buf: &mut [u8]) -> Result<usize> {
+	let n = socket.read(buf).await?;
+	budget::record(n);
+	Ok(n)
+}
+
+/// Read from a TCP socket until `buf` is full, the peer closes the
+/// connection, or `deadline` passes.
+///
+/// For line-based protocols where a response may arrive across several
+/// TCP segments.
+///
+/// # Returns
+/// Number of bytes read, possibly fewer than `buf.len()` (including `0`).
+///
+/// # Errors
+/// Returns `Error::Socket(SocketError::ConnectionReset)` if the connection
+/// was reset.
+pub async fn read_with_deadline(
+	socket: &mut TcpSocket<'_>,
+	buf: &mut [u8],
+	deadline: Instant,
+) -> Result<usize> {
+	let mut filled = 0;
+	while filled < buf.len() {
+		match with_deadline(deadline, socket.read(&mut buf[filled..])).await {
+			Ok(Ok(0)) | Err(_) => break, // Closed by peer, or deadline passed
+			Ok(Ok(n)) => {
+				budget::record(n);
+				filled += n;
+			}
+			Ok(Err(e)) => return Err(e.into()),
+		}
+	}
+	Ok(filled)
+}
+
+/// Size of the big-endian length prefix used by `send_framed` and
+/// `recv_framed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameHeader {
+	/// 2-byte prefix, payloads up to 65535 bytes
+	U16,
+	/// 4-byte prefix
+	U32,
+}
+
+impl FrameHeader {
+	/// Length of the prefix in bytes.
+	pub const fn prefix_len(self) -> usize {
+		match self {
+			Self::U16 => 2,
+			Self::U32 => 4,
+		}
+	}
+}
+
+/// Send `payload` as one length-prefixed frame.
+///
+/// # Errors
+/// Returns `Error::Config` if `payload` is too long for `header`, or the
+/// socket error.
+pub async fn send_framed(
+	socket: &mut TcpSocket<'_>,
+	header: FrameHeader,
+	payload: &[u8],
+) -> Result<()> {
+	let len = match header {
+		FrameHeader::U16 => u16::try_from(payload.len()).map(u32::from),
+		FrameHeader::U32 => u32::try_from(payload.len()),
+	}
+	.map_err(|_| Error::Config)?;
+
+	let prefix = len.to_be_bytes();
+	tcp_write_all(socket, &prefix[prefix.len() - header.prefix_len()..]).await?;
+	tcp_write_all(socket, payload).await
+}
+
+/// Receive one length-prefixed frame into `out`.
+///
+/// Reads until the whole frame has arrived, across as many TCP reads as
+/// needed.
+///
+/// # Returns
+/// The payload length.
+///
+/// # Errors
+/// Returns `Error::Config` if the frame is larger than `out` (the stream is
+/// then out of sync and the connection should be closed),
+/// `Error::Socket(SocketError::Closed)` if the peer closes the connection
+/// mid-frame, or the socket error.
+pub async fn recv_framed(
+	socket: &mut TcpSocket<'_>,
+	header: FrameHeader,
+	out: &mut [u8],
+) -> Result<usize> {
+	let mut prefix = [0u8; 4];
+	let start = prefix.len() - header.prefix_len();
+	read_exact(socket, &mut prefix[start..]).await?;
+
+	let len = u32::from_be_bytes(prefix) as usize;
+	let payload = out.get_mut(..len).ok_or(Error::Config)?;
+	read_exact(socket, payload).await?;
+	Ok(len)
+}
+
+/// Fill `buf` completely from a TCP socket.
+///
+/// # Errors
+/// Returns `Error::Socket(SocketError::Closed)` if the peer closes the
+/// connection first, or the socket error.
+async fn read_exact(socket: &mut TcpSocket<'_>, buf: &mut [u8]) -> Result<()> {
+	let mut filled = 0;
+	while filled < buf.len() {
+		match tcp_read(socket, &mut buf[filled..]).await? {
+			0 => return Err(Error::Socket(SocketError::Closed)),
+			n => filled += n,
+		}
+	}
+	Ok(())
+}
+
+/// Reads CRLF-terminated lines from a TCP socket.
+///
+/// Shared by the text protocols (SMTP, IMAP, HTTP headers). Lines may span
+/// several TCP reads; bytes after a line are kept for the next call.
+pub struct LineReader<'s, 'd, 'b> {
+	socket: &'s mut TcpSocket<'d>,
+	buf: &'b mut [u8],
+	/// Start of unconsumed data in `buf`
+	start: usize,
+	/// End of valid data in `buf`
+	end: usize,
+}
+
+impl<'s, 'd, 'b> LineReader<'s, 'd, 'b> {
+	/// Create a line reader over `socket`. `buf` bounds the line length.
+	pub fn new(socket: &'s mut TcpSocket<'d>, buf: &'b mut [u8]) -> Self {
+		Self {
+			socket,
+			buf,
+			start: 0,
+			end: 0,
+		}
+	}
+
+	/// Data read past the last returned line, e.g. the start of an HTTP
+	/// body following the headers.
+	pub fn buffered(&self) -> &[u8] {
+		&self.buf[self.start..self.end]
+	}
+
+	/// Read the next line, without its CRLF terminator.
+	///
+	/// # Errors
+	/// Returns `Error::Config` if a line does not fit in the buffer,
+	/// `Error::Socket(SocketError::Closed)` if the peer closes the connection
+	/// before the line is complete, or the socket error.
+	pub async fn next_line(&mut self) -> Result<&[u8]> {
+		let mut searched = self.start;
+		loop {
+			let pending = &self.buf[searched..self.end];
+			if let Some(pos) = pending.windows(2).position(|w| w == b"\r\n") {
+				let line_start = self.start;
+				let line_end = searched + pos;
+				self.start = line_end + 2;
+				return Ok(&self.buf[line_start..line_end]);
+			}
+			// Resume the search at a possible split "\r" next time
+			searched = self.end.saturating_sub(1).max(self.start);
+
+			// Move unconsumed data to the front to make room
+			if self.start > 0 {
+				self.buf.copy_within(self.start..self.end, 0);
+				self.end -= self.start;
+				searched -= self.start;
+				self.start = 0;
+			}
+			if self.end == self.buf.len() {
+				return Err(Error::Config);
+			}
+
+			match tcp_read(self.socket, &mut self.buf[self.end..]).await? {
+				0 => return Err(Error::Socket(SocketError::Closed)),
+				n => self.end += n,
+			}
+		}
+	}
+}
+
+/// Byte and timing accounting for one connection.
+///
+/// Wraps the TCP read/write helpers so higher-level operations can report
+/// how much was transferred and how long it took. On NB-IoT this separates
+/// a slow server from a slow link.
+#[derive(Clone, Copy, Debug)]
+pub struct TransferStats {
+	/// Bytes written to the socket
+	pub bytes_sent: u32,
+	/// Bytes read from the socket
+	pub bytes_received: u32,
+	started: Instant,
+	duration: Option<Duration>,
+}
+
+impl TransferStats {
+	/// Start accounting a transfer now.
+	pub fn start() -> Self {
+		Self {
+			bytes_sent: 0,
+			bytes_received: 0,
+			started: Instant::now(),
+			duration: None,
+		}
+	}
+
+	/// Stop the clock. Later reads and writes are still counted.
+	pub fn finish(&mut self) {
+		self.duration.get_or_insert(self.started.elapsed());
+	}
+
+	/// Time from `start` to `finish`, or until now if not finished.
+	pub fn duration(&self) -> Duration {
+		self.duration.unwrap_or_else(|| self.started.elapsed())
+	}
+
+	/// Average throughput in both directions, in bytes per second.
+	pub fn throughput(&self) -> u32 {
+		let total = u64::from(self.bytes_sent) + u64::from(self.bytes_received);
+		let ms = self.duration().as_millis().max(1);
+		(total * 1000 / ms).min(u64::from(u32::MAX)) as u32
+	}
+
+	/// `tcp_write_all`, counting the bytes sent.
+	pub async fn write_all(&mut self, socket: &mut TcpSocket<'_>, data: &[u8]) -> Result<()> {
+		tcp_write_all(socket, data).await?;
+		self.bytes_sent = self.bytes_sent.saturating_add(data.len() as u32);
+		Ok(())
+	}
+
+	/// `tcp_read`, counting the bytes received.
+	pub async fn read(&mut self, socket: &mut TcpSocket<'_>, buf: &mut [u8]) -> Result<usize> {
+		let n = tcp_read(socket, buf).await?;
+		self.bytes_received = self.bytes_received.saturating_add(n as u32);
+		Ok(n)
+	}
+}
+
+/// Handler for connections accepted by `listen()`.
+#[allow(async_fn_in_trait)]
+pub trait ConnectionHandler {
+	/// Serve one accepted connection.
+	///
+	/// The socket is closed by `listen()` once this returns.
+	async fn handle(&mut self, socket: &mut TcpSocket<'_>) -> Result<()>;
+}
+
+/// Accept TCP connections on `port` and serve them one at a time.
+///
+/// Each accepted connection is passed to `handler`, then closed. Errors from
+/// the handler or a peer reset end only that connection. Connections idle
+/// for longer than 30 seconds are dropped. See the module docs for APN/NAT
+/// caveats.
+///
+/// # Returns
+/// Only returns on an error that prevents listening.
+///
+/// # Errors
+/// Returns `Error::Socket(SocketError::InvalidState)` if `port` is invalid.
+pub async fn listen<H: ConnectionHandler>(
+	stack: &Stack<'_>,
+	port: u16,
+	handler: &mut H,
+) -> Result<()> {
+	let mut rx_buffer = [0u8; LISTEN_BUF_SIZE];
+	let mut tx_buffer = [0u8; LISTEN_BUF_SIZE];
+
+	loop {
+		let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+		socket.set_timeout(Some(LISTEN_TIMEOUT));
+
+		match socket.accept(port).await {
+			Ok(()) => {
+				let _ = handler.handle(&mut socket).await;
+				socket.close();
+				let _ = socket.flush().await;
+			}
+			Err(embassy_net::tcp::AcceptError::ConnectionReset) => {}
+			Err(e) => return Err(e.into()),
+		}
+		socket.abort();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn formatted(ip: [u8; 4]) -> String<IPV4_STR_MAX> {
+		let mut buf = String::new();
+		fmt_ipv4(&mut buf, ip).unwrap();
+		buf
+	}
+
+	#[test]
+	fn ipv4_dotted_quad() {
+		assert_eq!(formatted([0, 0, 0, 0]).as_str(), "0.0.0.0");
+		assert_eq!(formatted([10, 0, 0, 130]).as_str(), "10.0.0.130");
+		assert_eq!(formatted([255, 255, 255, 255]).as_str(), "255.255.255.255");
+	}
+
+	#[test]
+	fn ipv4_truncated() {
+		let mut buf: String<8> = String::new();
+		assert_eq!(fmt_ipv4(&mut buf, [192, 168, 100, 1]), Err(()));
+		assert_eq!(buf.as_str(), "192.168.");
+	}
+}
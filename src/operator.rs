@@ -0,0 +1,308 @@
+//! Network operator (PLMN) information.
+//!
+//! Reads the registered operator with `AT+COPS?` and exposes both the
+//! numeric PLMN (MCC+MNC) and, when available, the operator name.
+//!
+//! ## Fast Reattach
+//! The last PLMN the device registered on can be remembered with
+//! `remember_plmn()`. After a reboot, when nothing is remembered yet, the
+//! PLMN of the last registration stored on the SIM is used instead (see
+//! `read_stored_plmn()`). On wake, `select_last_plmn()` asks the modem to
+//! try that network first, falling back to automatic selection if it does
+//! not register quickly; `pdp::wake_and_ensure_connected()` does so when
+//! the modem is not registered. This saves seconds for devices that wake up
+//! in the same place. Automatic selection is restored either way, so the
+//! modem can still move to another network later.
+
+#![allow(dead_code)]
+
+use crate::at_builder::AtCommandBuilder;
+use crate::error::{Error, Result};
+use crate::modem::{at_command, at_command_ok, at_response, extract_value};
+use crate::registration;
+
+use core::cell::RefCell;
+
+use embassy_net_nrf91::Control;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Duration;
+use heapless::String;
+
+/// Maximum length of an operator name.
+pub const NAME_MAX: usize = 32;
+
+/// `<format>` values of `AT+COPS`.
+const FORMAT_LONG_ALPHA: u8 = 0;
+const FORMAT_NUMERIC: u8 = 2;
+
+/// Registered network operator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operator {
+	/// Numeric PLMN (MCC followed by 2 or 3 digit MNC), e.g. `"26201"`
+	pub plmn: String<6>,
+	/// Operator name, from the modem or the built-in table
+	pub name: Option<String<NAME_MAX>>,
+}
+
+/// Well-known PLMNs, used when the modem does not report a name.
+const KNOWN_OPERATORS: &[(&str, &str)] = &[
+	("20404", "Vodafone NL"),
+	("20408", "KPN"),
+	("20801", "Orange F"),
+	("20810", "SFR"),
+	("21401", "Vodafone ES"),
+	("21407", "Movistar"),
+	("22201", "TIM"),
+	("22210", "Vodafone IT"),
+	("23410", "O2 UK"),
+	("23415", "Vodafone UK"),
+	("23420", "Three UK"),
+	("23430", "EE"),
+	("24001", "Telia SE"),
+	("24008", "Telenor SE"),
+	("24201", "Telenor NO"),
+	("24202", "Telia NO"),
+	("26201", "Telekom.de"),
+	("26202", "Vodafone.de"),
+	("26203", "O2 DE"),
+	("310260", "T-Mobile US"),
+	("310410", "AT&T"),
+	("311480", "Verizon"),
+];
+
+/// Look up the name of a well-known PLMN.
+pub fn lookup_name(plmn: &str) -> Option<&'static str> {
+	KNOWN_OPERATORS
+		.iter()
+		.find(|(code, _)| *code == plmn)
+		.map(|(_, name)| *name)
+}
+
+/// Parse an `AT+COPS?` response.
+///
+/// Format: `+COPS: <mode>[,<format>,"<oper>"[,<AcT>]]`
+///
+/// # Returns
+/// `(format, oper)`, or `None` if no operator is selected.
+pub fn parse_cops(response: &str) -> Option<(u8, &str)> {
+	let pos = response.find("+COPS:")?;
+	let line = response[pos + 6..].lines().next()?;
+
+	let mut fields = line.splitn(3, ',');
+	let _mode = fields.next()?;
+	let format: u8 = fields.next()?.trim().parse().ok()?;
+
+	// Operator is quoted and may itself contain commas
+	let rest = fields.next()?;
+	let start = rest.find('"')? + 1;
+	let end = rest[start..].find('"')? + start;
+	Some((format, &rest[start..end]))
+}
+
+/// Query `AT+COPS?` and return the format and operator as owned strings.
+async fn query_cops(control: &Control<'_>) -> Result<(u8, String<NAME_MAX>)> {
+	let mut resp_buf = [0u8; 128];
+	let len = at_command(control, "AT+COPS?", &mut resp_buf).await;
+	let resp = core::str::from_utf8(&resp_buf[..len]).map_err(|_| Error::InvalidResponse)?;
+
+	let (format, oper) = parse_cops(resp).ok_or(Error::InvalidResponse)?;
+	let oper = String::try_from(oper).map_err(|_| Error::InvalidResponse)?;
+	Ok((format, oper))
+}
+
+/// Query `AT+COPS?` after switching the reported operator format.
+async fn query_cops_format(control: &Control<'_>, format: u8) -> Result<(u8, String<NAME_MAX>)> {
+	let cmd = if format == FORMAT_NUMERIC {
+		"AT+COPS=3,2"
+	} else {
+		"AT+COPS=3,0"
+	};
+	at_command_ok(control, cmd).await?;
+	query_cops(control).await
+}
+
+/// Get the registered network operator.
+///
+/// Issues `AT+COPS?` and, depending on the reported `<format>`, re-queries
+/// in the other format to get both the numeric PLMN and the name. If the
+/// modem does not provide a name, the built-in table is consulted. The
+/// numeric format is restored afterwards.
+///
+/// # Returns
+/// The operator, or `Err(Error::InvalidResponse)` if not registered or the
+/// response cannot be parsed.
+pub async fn get_operator(control: &Control<'_>) -> Result<Operator> {
+	let (format, oper) = query_cops(control).await?;
+
+	let (plmn, mut name) = if format == FORMAT_NUMERIC {
+		let name = match query_cops_format(control, FORMAT_LONG_ALPHA).await {
+			Ok((FORMAT_LONG_ALPHA, name)) if name.as_str() != oper.as_str() => {
+				Some(name)
+			}
+			_ => None,
+		};
+		let _ = at_command_ok(control, "AT+COPS=3,2").await;
+		(oper, name)
+	} else {
+		let (_, plmn) = query_cops_format(control, FORMAT_NUMERIC).await?;
+		(plmn, Some(oper))
+	};
+
+	if name.is_none() {
+		name = lookup_name(&plmn).and_then(|known| String::try_from(known).ok());
+	}
+
+	Ok(Operator {
+		plmn: String::try_from(plmn.as_str()).map_err(|_| Error::InvalidResponse)?,
+		name,
+	})
+}
+
+/// PLMN of the last successful registration.
+static LAST_PLMN: Mutex<CriticalSectionRawMutex, RefCell<Option<String<6>>>> =
+	Mutex::new(RefCell::new(None));
+
+/// Remember the PLMN the device registered on, for `select_last_plmn()`.
+pub fn remember_plmn(plmn: &str) {
+	let plmn = String::try_from(plmn).ok();
+	LAST_PLMN.lock(|last| *last.borrow_mut() = plmn);
+}
+
+/// Get the remembered PLMN, if any.
+pub fn last_plmn() -> Option<String<6>> {
+	LAST_PLMN.lock(|last| last.borrow().clone())
+}
+
+/// SIM file holding the last registered EPS location (EF_EPSLOCI, see
+/// 3GPP TS 31.102).
+const EF_EPSLOCI: u16 = 0x6FE3;
+
+/// Length of EF_EPSLOCI: GUTI, last visited registered TAI, update status.
+const EPSLOCI_LEN: u8 = 18;
+
+/// Offset of the TAI's PLMN in EF_EPSLOCI, after the 12-byte GUTI.
+const EPSLOCI_PLMN_OFFSET: usize = 12;
+
+/// `AT+CRSM` command for reading a transparent file.
+const CRSM_READ_BINARY: u8 = 176;
+
+/// `<sw1>` of a successful `AT+CRSM` command.
+const CRSM_SW1_OK: u8 = 144;
+
+/// Parse an `AT+CRSM` read of EF_EPSLOCI into the PLMN it holds.
+///
+/// Format: `+CRSM: <sw1>,<sw2>,"<hex data>"`. The PLMN is 3 bytes of
+/// BCD digits with swapped nibbles (`MCC2 MCC1`, `MNC3 MCC3`, `MNC2 MNC1`),
+/// with `F` for the third MNC digit of 2-digit MNCs.
+///
+/// # Returns
+/// The PLMN, or `None` if the read failed or the SIM holds no valid
+/// location (all `F`s after a detach).
+pub fn parse_epsloci_plmn(response: &str) -> Option<String<6>> {
+	let value = extract_value(response, "+CRSM:")?;
+	let mut fields = value.split(',').map(|field| field.trim().trim_matches('"'));
+
+	let sw1: u8 = fields.next()?.parse().ok()?;
+	if sw1 != CRSM_SW1_OK {
+		return None;
+	}
+	let data = fields.nth(1)?.as_bytes();
+	let bcd = data.get(EPSLOCI_PLMN_OFFSET * 2..EPSLOCI_PLMN_OFFSET * 2 + 6)?;
+
+	let mut plmn = String::new();
+	for &digit in &[bcd[1], bcd[0], bcd[3], bcd[5], bcd[4], bcd[2]] {
+		match digit {
+			b'0'..=b'9' => plmn.push(char::from(digit)).ok()?,
+			// Only the third MNC digit may be absent
+			b'F' | b'f' if plmn.len() == 5 => {}
+			_ => return None,
+		}
+	}
+	Some(plmn)
+}
+
+/// Read the PLMN of the last registration stored on the SIM.
+///
+/// Unlike the remembered PLMN, this survives reboots and power loss.
+///
+/// # Errors
+/// Returns `Error::InvalidResponse` if the file cannot be read or holds no
+/// valid location.
+pub async fn read_stored_plmn(control: &Control<'_>) -> Result<String<6>> {
+	let cmd = AtCommandBuilder::<32>::new("AT+CRSM")?
+		.num(CRSM_READ_BINARY)?
+		.num(EF_EPSLOCI)?
+		.num(0u8)?
+		.num(0u8)?
+		.num(EPSLOCI_LEN)?;
+	let response = at_response(control, cmd.as_str()).await?;
+	let resp = core::str::from_utf8(&response).map_err(|_| Error::InvalidResponse)?;
+	parse_epsloci_plmn(resp).ok_or(Error::InvalidResponse)
+}
+
+/// Try to register on the last used PLMN before automatic selection.
+///
+/// Takes the remembered PLMN, or the one stored on the SIM if none is
+/// remembered, selects it manually (`AT+COPS=1`) and waits up to
+/// `quick_timeout` for registration. Automatic selection (`AT+COPS=0`) is
+/// restored afterwards in any case: after a quick attach the modem stays
+/// on the network it registered on, and otherwise registration continues
+/// as usual.
+///
+/// # Returns
+/// `Ok(true)` if registered on the last used PLMN, `Ok(false)` if fell back
+/// to automatic selection.
+///
+/// # Errors
+/// Returns `Error::AtCommand` if automatic selection cannot be restored.
+pub async fn select_last_plmn(control: &Control<'_>, quick_timeout: Duration) -> Result<bool> {
+	let plmn = match last_plmn() {
+		Some(plmn) => Some(plmn),
+		None => read_stored_plmn(control).await.ok(),
+	};
+
+	let mut attached = false;
+	if let Some(plmn) = plmn {
+		let cmd = AtCommandBuilder::<24>::new("AT+COPS")?
+			.num(1u8)?
+			.num(FORMAT_NUMERIC)?
+			.string(&plmn)?;
+		attached = at_command_ok(control, cmd.as_str()).await.is_ok()
+			&& registration::await_attach(quick_timeout).await.is_ok();
+		if attached {
+			remember_plmn(&plmn);
+		}
+	}
+
+	at_command_ok(control, "AT+COPS=0").await?;
+	Ok(attached)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn epsloci_two_digit_mnc() {
+		// 24202 in the TAI, TAC 0x0901
+		let resp = "+CRSM: 144,0,\"0BF6420F220001112233445542F220090100\"\r\nOK\r\n";
+		assert_eq!(parse_epsloci_plmn(resp).as_deref(), Some("24202"));
+	}
+
+	#[test]
+	fn epsloci_three_digit_mnc() {
+		// 310410 in the TAI
+		let resp = "+CRSM: 144,0,\"0BF6130014000111223344551300140A1B00\"\r\nOK\r\n";
+		assert_eq!(parse_epsloci_plmn(resp).as_deref(), Some("310410"));
+	}
+
+	#[test]
+	fn epsloci_no_location() {
+		let resp = "+CRSM: 144,0,\"FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF01\"\r\nOK\r\n";
+		assert_eq!(parse_epsloci_plmn(resp), None);
+
+		let resp = "+CRSM: 106,130,\"\"\r\nOK\r\n";
+		assert_eq!(parse_epsloci_plmn(resp), None);
+	}
+}
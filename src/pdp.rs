@@ -3,6 +3,12 @@
 //! This module handles PDP (Packet Data Protocol) context activation
 //! which is required for IP connectivity over cellular networks.
 //!
+//! ## Packet Domain Events
+//! `enable_packet_events()` turns on `+CGEV` reporting and `parse_cgev()`
+//! turns a `+CGEV` line into a `PacketEvent`. The driver does not deliver
+//! unsolicited result codes, so events are only seen where they appear in
+//! a command response.
+//!
 //! ## Error Handling
 //! Functions return `Result<T, Error>` where errors should be handled
 //! by the caller. For fatal errors, use the `fatal_error!` macro.
@@ -11,10 +17,26 @@
 
 use crate::error::{Error, Result};
 
-use embassy_net::{ConfigV4, Ipv4Address, Ipv4Cidr, Stack, StaticConfigV4};
+use core::net::Ipv6Addr;
+
+use embassy_net::{Ipv4Address, Stack};
 use embassy_net_nrf91::Control;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration};
+
+use crate::at_builder::AtCommandBuilder;
+use crate::backoff::Backoff;
+use crate::cancel::{with_timeout_cancellable, CancelToken};
+use crate::config::Config;
+use crate::registration;
+
+/// Maximum time `wake_and_ensure_connected` may take.
+const WAKE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time to wait for registration on the last used network after waking,
+/// before falling back to automatic network selection.
+const PLMN_QUICK_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Signal for PDP context status changes.
 pub static PDP_STATUS_SIGNAL: Signal<CriticalSectionRawMutex, PdpStatus> = Signal::new();
@@ -26,8 +48,67 @@ pub enum PdpStatus {
 	Deactivated,
 	/// Context is activated with IP address
 	Activated { ip: Ipv4Address },
+	/// Context was re-activated with a different IP address.
+	/// Sockets and server sessions bound to the old address are stale.
+	AddressChanged { old: Ipv4Address, new: Ipv4Address },
 }
 
+impl PdpStatus {
+	/// Status for a (re-)activated context, given the previous address.
+	fn activated(previous: Option<Ipv4Address>, ip: Ipv4Address) -> Self {
+		match previous {
+			Some(old) if old != ip => Self::AddressChanged { old, new: ip },
+			_ => Self::Activated { ip },
+		}
+	}
+}
+
+/// IP configuration of an active PDP context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpConfig {
+	/// Address assigned to the device
+	pub address: Ipv4Address,
+	/// Subnet prefix length
+	pub prefix_len: u8,
+	/// Default gateway, if provided by the network
+	pub gateway: Option<Ipv4Address>,
+	/// Primary and secondary DNS servers, if provided by the network
+	pub dns: [Option<Ipv4Address>; 2],
+	/// IPv4 link MTU, if provided by the network
+	pub mtu: Option<u16>,
+	/// IPv6 address, for dual-stack and IPv6 contexts
+	pub address_v6: Option<Ipv6Addr>,
+	/// Primary and secondary IPv6 DNS servers, if provided by the network
+	pub dns_v6: [Option<Ipv6Addr>; 2],
+}
+
+impl IpConfig {
+	/// Configuration with only an address, as known from `AT+CGPADDR`.
+	pub fn from_address(address: Ipv4Address) -> Self {
+		Self {
+			address,
+			prefix_len: DEFAULT_PREFIX_LEN,
+			gateway: None,
+			dns: [None, None],
+			mtu: None,
+			address_v6: None,
+			dns_v6: [None, None],
+		}
+	}
+}
+
+/// Prefix length used when the network does not report a subnet mask.
+const DEFAULT_PREFIX_LEN: u8 = 24;
+
+/// Prefix length of the IPv6 address; cellular networks assign a /64.
+const IPV6_PREFIX_LEN: u8 = 64;
+
+/// Delay before trying the next APN, doubled for each further one.
+const APN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between APNs.
+const APN_RETRY_MAX_DELAY: Duration = Duration::from_secs(8);
+
 /// Activate PDP context (data connection).
 ///
 /// For nRF91, the default PDP context (CID 0) is typically auto-activated
@@ -50,13 +131,12 @@ pub async fn activate<'a>(control: &Control<'a>) -> Result<Ipv4Address> {
 
 	// If not auto-activated, try manual activation
 	// Configure PDP context with default APN (uses SIM settings)
-	let _ = control
-		.at_command(b"AT+CGDCONT=0,\"IP\"", &mut resp_buf)
+	let _ = crate::modem::at_command_bytes(control, b"AT+CGDCONT=0,\"IP\"", &mut resp_buf)
 		.await;
 	embassy_time::Timer::after_millis(100).await;
 
 	// Activate PDP context
-	let len = control.at_command(b"AT+CGACT=1,0", &mut resp_buf).await;
+	let len = crate::modem::at_command_bytes(control, b"AT+CGACT=1,0", &mut resp_buf).await;
 	embassy_time::Timer::after_millis(1000).await;
 
 	if len > 0 {
@@ -74,13 +154,128 @@ pub async fn activate<'a>(control: &Control<'a>) -> Result<Ipv4Address> {
 	get_ip_address(control).await.ok_or(Error::PdpActivation)
 }
 
+/// APN authentication protocol (`AT+CGAUTH` `<auth_prot>`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthProtocol {
+	/// No authentication
+	None = 0,
+	/// Password Authentication Protocol
+	Pap = 1,
+	/// Challenge Handshake Authentication Protocol
+	Chap = 2,
+}
+
+/// APN credentials.
+#[derive(Clone, Copy, Debug)]
+pub struct ApnAuth<'a> {
+	/// Authentication protocol
+	pub protocol: AuthProtocol,
+	/// User name
+	pub username: &'a str,
+	/// Password
+	pub password: &'a str,
+}
+
+/// PDP context configuration.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PdpConfig<'a> {
+	/// APNs to try in order. An empty list uses the APN provided by the SIM.
+	pub apns: &'a [&'a str],
+	/// Credentials for APNs that require authentication
+	pub auth: Option<ApnAuth<'a>>,
+}
+
+/// Set the authentication used for a PDP context.
+///
+/// Must be called before the context is activated.
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::Config)` if the credentials are too
+/// long or contain `"`, or `Err(Error::AtCommand)` if the modem rejects
+/// them.
+pub async fn set_apn_auth(
+	control: &Control<'_>,
+	cid: u8,
+	protocol: AuthProtocol,
+	username: &str,
+	password: &str,
+) -> Result<()> {
+	let cmd = AtCommandBuilder::<160>::new("AT+CGAUTH")?
+		.num(cid)?
+		.num(protocol as u8)?;
+	let cmd = match protocol {
+		AuthProtocol::None => cmd,
+		_ => cmd.string(username)?.string(password)?,
+	};
+
+	crate::modem::at_command_ok(control, cmd.as_str()).await
+}
+
+/// Activate PDP context using the given configuration.
+///
+/// Each APN in `config.apns` is tried in order until one yields an IP
+/// address, with a growing pause between them (`APN_RETRY_DELAY` up to
+/// `APN_RETRY_MAX_DELAY`). Roaming SIMs often need a fallback APN in some
+/// regions. Credentials in `config.auth` are applied before activation.
+///
+/// # Returns
+/// `Ok((ip_address, apn))` with the APN that succeeded (`None` when the SIM
+/// default was used), or `Err(Error::PdpActivation)` if every APN failed.
+pub async fn activate_with_config<'c>(
+	control: &Control<'_>,
+	config: &PdpConfig<'c>,
+) -> Result<(Ipv4Address, Option<&'c str>)> {
+	if let Some(auth) = config.auth {
+		set_apn_auth(control, 0, auth.protocol, auth.username, auth.password)
+			.await
+			.map_err(|_| Error::PdpActivation)?;
+	}
+
+	if config.apns.is_empty() {
+		return activate(control).await.map(|ip| (ip, None));
+	}
+
+	// Back off between APNs so a network rejecting activations is not hammered
+	let mut backoff = Backoff::new(APN_RETRY_DELAY, APN_RETRY_MAX_DELAY);
+	for (i, &apn) in config.apns.iter().enumerate() {
+		if i > 0 {
+			embassy_time::Timer::after(backoff.next_delay()).await;
+		}
+		if let Ok(ip) = activate_apn(control, apn).await {
+			return Ok((ip, Some(apn)));
+		}
+		// Clean up before trying the next APN
+		let _ = deactivate(control).await;
+	}
+
+	Err(Error::PdpActivation)
+}
+
+/// Configure the default PDP context with `apn` and activate it.
+async fn activate_apn(control: &Control<'_>, apn: &str) -> Result<Ipv4Address> {
+	let mut resp_buf = [0u8; 128];
+
+	let cmd = AtCommandBuilder::<96>::new("AT+CGDCONT")?
+		.num(0u8)?
+		.string("IP")?
+		.string(apn)?;
+	let _ = crate::modem::at_command_bytes(control, cmd.as_str().as_bytes(), &mut resp_buf)
+		.await;
+	embassy_time::Timer::after_millis(100).await;
+
+	let _ = crate::modem::at_command_bytes(control, b"AT+CGACT=1,0", &mut resp_buf).await;
+	embassy_time::Timer::after_millis(1000).await;
+
+	get_ip_address(control).await.ok_or(Error::PdpActivation)
+}
+
 /// Deactivate PDP context.
 ///
 /// # Returns
 /// `Ok(())` on success, `Err(Error::PdpActivation)` on failure.
 pub async fn deactivate<'a>(control: &Control<'a>) -> Result<()> {
 	let mut resp_buf = [0u8; 128];
-	let len = control.at_command(b"AT+CGACT=0,0", &mut resp_buf).await;
+	let len = crate::modem::at_command_bytes(control, b"AT+CGACT=0,0", &mut resp_buf).await;
 
 	if len > 0 {
 		if let Ok(resp) = core::str::from_utf8(&resp_buf[..len]) {
@@ -94,37 +289,93 @@ pub async fn deactivate<'a>(control: &Control<'a>) -> Result<()> {
 
 /// Get the IP address assigned to the PDP context.
 pub async fn get_ip_address<'a>(control: &Control<'a>) -> Option<Ipv4Address> {
-	let mut resp_buf = [0u8; 256];
+	get_ip_addresses(control).await.ipv4
+}
 
-	// Query PDP context addresses
-	let len = control.at_command(b"AT+CGPADDR=0", &mut resp_buf).await;
+/// Addresses assigned to a PDP context.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PdpAddresses {
+	/// IPv4 address, if the context has one
+	pub ipv4: Option<Ipv4Address>,
+	/// IPv6 address, if the context has one
+	pub ipv6: Option<Ipv6Addr>,
+}
 
-	if len > 0 {
-		if let Ok(resp) = core::str::from_utf8(&resp_buf[..len]) {
-			return parse_cgpaddr_response(resp);
+/// Get the IPv4 and IPv6 addresses assigned to the PDP context.
+///
+/// For dual-stack contexts both are set; for single-stack contexts only
+/// one of them is.
+pub async fn get_ip_addresses<'a>(control: &Control<'a>) -> PdpAddresses {
+	// Query PDP context addresses
+	if let Ok(response) = crate::modem::at_response(control, "AT+CGPADDR=0").await {
+		if let Ok(resp) = core::str::from_utf8(&response) {
+			return parse_cgpaddr(resp);
 		}
 	}
-	None
+	PdpAddresses::default()
 }
 
 /// Parse +CGPADDR response to extract IP address.
 /// Format: +CGPADDR: 0,"10.160.x.x"
 fn parse_cgpaddr_response(response: &str) -> Option<Ipv4Address> {
-	// Find +CGPADDR: in response
-	let cgpaddr_pos = response.find("+CGPADDR:")?;
-	let after = &response[cgpaddr_pos + 9..];
+	parse_cgpaddr(response).ipv4
+}
+
+/// Parse +CGPADDR response to extract all addresses.
+///
+/// Format: `+CGPADDR: <cid>,"<addr>"[,"<addr>"]`, e.g. for a dual-stack
+/// context: `+CGPADDR: 0,"10.0.0.2","2001:DB8::1"`. Each address may be
+/// IPv4 or IPv6, in either order.
+fn parse_cgpaddr(response: &str) -> PdpAddresses {
+	let mut addresses = PdpAddresses::default();
+
+	let Some(cgpaddr_pos) = response.find("+CGPADDR:") else {
+		return addresses;
+	};
+	let line = response[cgpaddr_pos + 9..].lines().next().unwrap_or("");
 
-	// Find the IP address in quotes
-	let quote_start = after.find('"')? + 1;
-	let quote_end = after[quote_start..].find('"')? + quote_start;
-	let ip_str = &after[quote_start..quote_end];
+	// Quoted fields are every other segment between quotes
+	for field in line.split('"').skip(1).step_by(2) {
+		if let Some(ip) = parse_ipv4(field) {
+			addresses.ipv4.get_or_insert(ip);
+		} else if let Some(ip) = parse_ipv6(field) {
+			addresses.ipv6.get_or_insert(ip);
+		}
+	}
+	addresses
+}
 
-	// Parse IP address
-	parse_ipv4(ip_str)
+/// Parse an IPv6 address string.
+///
+/// Accepts standard colon-hex notation as well as the nRF91 notation of
+/// 16 decimal octets separated by dots (e.g. `32.1.13.184.0.0.0.0.0.0.0.0.0.0.0.1`).
+fn parse_ipv6(s: &str) -> Option<Ipv6Addr> {
+	let s =
+		s.trim().trim_matches(|c| matches!(c, '"' | '[' | ']'))
+			.trim();
+	if s.contains(':') {
+		return s.parse().ok();
+	}
+
+	let mut octets = [0u8; 16];
+	let mut parts = s.split('.');
+	for octet in octets.iter_mut() {
+		*octet = parts.next()?.parse().ok()?;
+	}
+	if parts.next().is_some() {
+		return None; // Too many parts
+	}
+	Some(Ipv6Addr::from(octets))
 }
 
 /// Parse an IPv4 address string.
+///
+/// Tolerates surrounding whitespace, quotes and brackets, since the
+/// address fields of `CGPADDR`/`CGCONTRDP` vary between firmware versions.
 fn parse_ipv4(s: &str) -> Option<Ipv4Address> {
+	let s =
+		s.trim().trim_matches(|c| matches!(c, '"' | '[' | ']'))
+			.trim();
 	let mut parts = s.split('.');
 	let a: u8 = parts.next()?.parse().ok()?;
 	let b: u8 = parts.next()?.parse().ok()?;
@@ -139,13 +390,349 @@ fn parse_ipv4(s: &str) -> Option<Ipv4Address> {
 }
 
 /// Configure the network stack with PDP context IP address.
+///
+/// DNS servers set with `network::set_dns_servers` are applied.
 pub fn configure_stack(stack: &Stack<'_>, ip: Ipv4Address, gateway: Option<Ipv4Address>) {
-	let static_config = StaticConfigV4 {
-		address: Ipv4Cidr::new(ip, 24),
-		gateway,
-		dns_servers: Default::default(),
+	crate::network::set_ipv4_config(stack, ip, gateway);
+}
+
+/// Configure the network stack with a full PDP context IP configuration.
+///
+/// Network-provided DNS servers are used unless overridden with
+/// `network::set_dns_servers`. The IPv6 configuration is only set if the
+/// context has an IPv6 address.
+pub fn configure_stack_with(stack: &Stack<'_>, config: &IpConfig) {
+	let dns: heapless::Vec<Ipv4Address, 2> = config.dns.iter().flatten().copied().collect();
+	crate::network::set_ipv4_config_with(
+		stack,
+		config.address,
+		config.prefix_len,
+		config.gateway,
+		&dns,
+	);
+
+	if let Some(address) = config.address_v6 {
+		let dns_v6: heapless::Vec<Ipv6Addr, 2> =
+			config.dns_v6.iter().flatten().copied().collect();
+		crate::network::set_ipv6_config_with(
+			stack,
+			address,
+			IPV6_PREFIX_LEN,
+			None,
+			&dns_v6,
+		);
+	}
+}
+
+/// Read the IP configuration of the default PDP context (`AT+CGCONTRDP=0`).
+///
+/// The local address is often left empty by the modem; it is then
+/// `Ipv4Address::UNSPECIFIED` and should be taken from `get_ip_address`.
+pub async fn get_context_config(control: &Control<'_>) -> Option<IpConfig> {
+	// A dual-stack context answers with two long lines, which fit in a
+	// pool buffer
+	let response = crate::modem::at_response(control, "AT+CGCONTRDP=0")
+		.await
+		.ok()?;
+	parse_cgcontrdp(core::str::from_utf8(&response).ok()?)
+}
+
+/// Parse a `+CGCONTRDP` response in a single pass.
+///
+/// Format: `+CGCONTRDP: <cid>,<bearer_id>,<apn>,<local_addr and subnet_mask>,
+/// <gw_addr>,<DNS_prim_addr>,<DNS_sec_addr>,<P-CSCF_prim_addr>,
+/// <P-CSCF_sec_addr>,<IM_CN_Signalling_Flag>,<LIPA_indication>,<IPv4_MTU>`
+///
+/// For example:
+/// `+CGCONTRDP: 0,,"internet","10.0.0.2.255.255.255.0","","8.8.8.8","8.8.4.4",,,,,1500`.
+/// The local address field holds the address followed by the subnet mask
+/// (8 dotted values) or only the address, and is often empty.
+///
+/// A dual-stack context has one line per address family. The address,
+/// gateway and MTU are taken from the first IPv4 line; IPv6 lines (16
+/// dotted octets in the local address, or IPv6 DNS servers) fill in
+/// `address_v6` and `dns_v6`.
+///
+/// # Returns
+/// The configuration, with `Ipv4Address::UNSPECIFIED` if the local address
+/// is missing, or `None` if there is no `+CGCONTRDP` line.
+pub fn parse_cgcontrdp(response: &str) -> Option<IpConfig> {
+	let mut config = IpConfig::from_address(Ipv4Address::UNSPECIFIED);
+	let mut found = false;
+	let mut have_v4 = false;
+
+	for line in response.lines() {
+		let Some(pos) = line.find("+CGCONTRDP:") else {
+			continue;
+		};
+		found = true;
+
+		let mut fields = line[pos + 11..]
+			.split(',')
+			.map(|field| field.trim().trim_matches('"'));
+
+		// <cid>, <bearer_id>, <apn>
+		if fields.nth(2).is_none() {
+			continue;
+		}
+		let local = fields.next().unwrap_or("");
+		let gateway = fields.next().unwrap_or("");
+		let dns = [fields.next().unwrap_or(""), fields.next().unwrap_or("")];
+
+		let local_parts = local.split('.').count();
+		if local_parts >= 16 || dns.iter().any(|server| parse_ipv6(server).is_some()) {
+			if local_parts >= 16 {
+				// Drop the subnet mask following the 16th octet, if any
+				let end = local
+					.match_indices('.')
+					.nth(15)
+					.map_or(local.len(), |(i, _)| i);
+				config.address_v6 = parse_ipv6(&local[..end]);
+			}
+			config.dns_v6[0] = parse_ipv6(dns[0]);
+			config.dns_v6[1] = parse_ipv6(dns[1]);
+			continue;
+		}
+		if have_v4 {
+			continue;
+		}
+		have_v4 = true;
+
+		let mut octets = local.split('.').map(|part| part.parse::<u8>());
+		let mut next_quad = || -> Option<Ipv4Address> {
+			let mut quad = [0u8; 4];
+			for octet in quad.iter_mut() {
+				*octet = octets.next()?.ok()?;
+			}
+			Some(Ipv4Address::from(quad))
+		};
+		if let Some(address) = next_quad() {
+			config.address = address;
+			if let Some(mask) = next_quad() {
+				config.prefix_len = u32::from(mask).count_ones() as u8;
+			}
+		}
+
+		config.gateway = parse_ipv4(gateway);
+		config.dns[0] = parse_ipv4(dns[0]);
+		config.dns[1] = parse_ipv4(dns[1]);
+
+		// <P-CSCF_prim_addr>, <P-CSCF_sec_addr>, <IM_CN_Signalling_Flag>, <LIPA_indication>
+		config.mtu = fields.nth(4).and_then(|mtu| mtu.parse().ok());
+	}
+
+	found.then_some(config)
+}
+
+/// Packet domain event from a `+CGEV` unsolicited result code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketEvent {
+	/// PDN connection activated (`ME PDN ACT <cid>`)
+	PdnActivated { cid: u8 },
+	/// PDN connection deactivated by the device (`ME PDN DEACT <cid>`)
+	PdnDeactivated { cid: u8 },
+	/// PDN connection deactivated by the network (`NW PDN DEACT <cid>`)
+	NwPdnDeactivated { cid: u8 },
+	/// Dedicated bearer activated (`ME ACT` or `NW ACT <p_cid>,<cid>,...`)
+	BearerActivated { cid: u8 },
+	/// Dedicated bearer deactivated (`ME DEACT` or `NW DEACT <p_cid>,<cid>,...`)
+	BearerDeactivated { cid: u8 },
+	/// Context parameters modified (`ME MODIFY` or `NW MODIFY <cid>,...`)
+	Modified { cid: u8 },
+	/// IPv6 link up, the address is usable (`IPV6 <cid>`)
+	Ipv6LinkUp { cid: u8 },
+	/// Detached by the network (`NW DETACH`)
+	Detached,
+	/// Detached by the device, e.g. on `AT+CFUN=4` (`ME DETACH`)
+	MeDetach,
+	/// APN rate control restriction (`RESTR <cause>,<validity>`)
+	Restricted,
+}
+
+impl PacketEvent {
+	/// Get the context ID the event refers to, if any.
+	pub fn cid(self) -> Option<u8> {
+		match self {
+			Self::PdnActivated { cid }
+			| Self::PdnDeactivated { cid }
+			| Self::NwPdnDeactivated { cid }
+			| Self::BearerActivated { cid }
+			| Self::BearerDeactivated { cid }
+			| Self::Modified { cid }
+			| Self::Ipv6LinkUp { cid } => Some(cid),
+			Self::Detached | Self::MeDetach | Self::Restricted => None,
+		}
+	}
+}
+
+/// Parse a `+CGEV` line, e.g. `+CGEV: ME PDN ACT 0,2` or `+CGEV: NW DETACH`.
+///
+/// Trailing parameters such as the activation reason are ignored.
+///
+/// # Returns
+/// The event, or `None` if the line is not a known `+CGEV` event.
+pub fn parse_cgev(line: &str) -> Option<PacketEvent> {
+	let event = line.trim().strip_prefix("+CGEV:")?.trim();
+	let mut words = event.split_whitespace();
+	let first = words.next()?;
+	let second = words.next();
+	let third = words.next();
+
+	// Parameters follow the keywords, e.g. `0,2`
+	let params = event
+		.split(' ')
+		.find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+		.unwrap_or("");
+	let mut numbers = params
+		.split(',')
+		.map(|param| param.trim().parse::<u8>().ok());
+	let mut number = || numbers.next().flatten();
+
+	let parsed = match (first, second, third) {
+		("ME", Some("PDN"), Some("ACT")) => PacketEvent::PdnActivated { cid: number()? },
+		("ME", Some("PDN"), Some("DEACT")) => {
+			PacketEvent::PdnDeactivated { cid: number()? }
+		}
+		("NW", Some("PDN"), Some("DEACT")) => {
+			PacketEvent::NwPdnDeactivated { cid: number()? }
+		}
+		("ME" | "NW", Some("ACT"), Some(_)) => {
+			let _p_cid = number()?;
+			PacketEvent::BearerActivated { cid: number()? }
+		}
+		("ME" | "NW", Some("DEACT"), Some(_)) => {
+			let _p_cid = number()?;
+			PacketEvent::BearerDeactivated { cid: number()? }
+		}
+		("ME" | "NW", Some("MODIFY"), Some(_)) => PacketEvent::Modified { cid: number()? },
+		("IPV6", Some(_), None) => PacketEvent::Ipv6LinkUp { cid: number()? },
+		("NW", Some("DETACH"), None) => PacketEvent::Detached,
+		("ME", Some("DETACH"), None) => PacketEvent::MeDetach,
+		("RESTR", Some(_), None) => PacketEvent::Restricted,
+		_ => return None,
+	};
+	Some(parsed)
+}
+
+/// Enable `+CGEV` packet domain event reporting.
+///
+/// Requests buffered reporting (`AT+CGEREP=2,1`), falling back to plain
+/// forwarding (`AT+CGEREP=1`) on modem firmware that only supports that.
+///
+/// # Returns
+/// `Ok(())` on success, `Err(Error::AtCommand)` if both are rejected.
+pub async fn enable_packet_events(control: &Control<'_>) -> Result<()> {
+	if crate::modem::at_command_ok(control, "AT+CGEREP=2,1")
+		.await
+		.is_ok()
+	{
+		return Ok(());
+	}
+	crate::modem::at_command_ok(control, "AT+CGEREP=1").await
+}
+
+/// Bring the data connection back up after waking from sleep.
+///
+/// With PSM the network may tear down the PDP context while the device
+/// sleeps. This checks registration, trying the last used network first if
+/// the modem is not registered (see `operator::select_last_plmn`),
+/// re-activates the context if the IP address is gone and reconfigures the
+/// stack from the context details if the address changed, all within
+/// `WAKE_TIMEOUT`. It also brings the connection back after the modem was
+/// taken offline for recovery.
+///
+/// Pass the `config` the device came online with: the context is
+/// re-activated through `activate_with_config`, so the same APNs and
+/// credentials apply.
+///
+/// # Returns
+/// The current IP configuration, `Err(Error::PdpActivation)` if the context
+/// could not be re-activated, or `Err(Error::Timeout)` if the device did not
+/// get online in time.
+pub async fn wake_and_ensure_connected(
+	control: &Control<'_>,
+	stack: &Stack<'_>,
+	config: &PdpConfig<'_>,
+) -> Result<IpConfig> {
+	let reconnect = async {
+		// Try the network used last before the modem searches all of them
+		let registered = registration::query_registration(control)
+			.await
+			.is_some_and(|status| status.is_registered());
+		if !registered {
+			let _ = crate::operator::select_last_plmn(control, PLMN_QUICK_TIMEOUT)
+				.await;
+		}
+
+		// Wait until the modem is registered again
+		let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(5));
+		loop {
+			match registration::query_registration(control).await {
+				Some(status) if status.is_registered() => break,
+				_ => embassy_time::Timer::after(backoff.next_delay()).await,
+			}
+		}
+
+		// Re-activate the context only if the address is gone
+		let ip = match get_ip_address(control).await {
+			Some(ip) => ip,
+			None => activate_with_config(control, config).await?.0,
+		};
+
+		// A new address comes with a new context: take its DNS servers,
+		// gateway and MTU along
+		let current = stack.config_v4().map(|config| config.address.address());
+		if current != Some(ip) {
+			let mut ip_config = get_context_config(control)
+				.await
+				.unwrap_or(IpConfig::from_address(ip));
+			ip_config.address = ip;
+			configure_stack_with(stack, &ip_config);
+			return Ok(ip_config);
+		}
+
+		Ok(IpConfig::from_address(ip))
+	};
+
+	with_timeout(WAKE_TIMEOUT, reconnect)
+		.await
+		.map_err(|_| Error::Timeout)?
+}
+
+/// Get the link ready ahead of a planned transmission.
+///
+/// Call this `lead_time` before the payload is due, so registration and
+/// PDP activation overlap with preparing the data and the send can start
+/// right away. See `wake_and_ensure_connected` for what is brought up.
+///
+/// With PSM granted, the modem returns to sleep once the active time runs
+/// out after activity. The granted active time is read from the modem
+/// (`modem::get_rf_status()`), or taken from
+/// `registration::negotiated_psm()` if that fails. If
+/// `lead_time` is longer than the active time, the wake-up is delayed so
+/// the modem is still awake when the payload is due, instead of waking
+/// early and dropping back into PSM just before the send. The radio
+/// connection itself only comes up with the first packet.
+///
+/// # Returns
+/// As `wake_and_ensure_connected`.
+pub async fn prewarm(
+	control: &Control<'_>,
+	stack: &Stack<'_>,
+	config: &PdpConfig<'_>,
+	lead_time: Duration,
+) -> Result<IpConfig> {
+	let active_time = match crate::modem::get_rf_status(control).await {
+		Ok(status) => status.active_time,
+		Err(_) => registration::negotiated_psm().map(|(_, active_time)| active_time),
 	};
-	stack.set_config_v4(ConfigV4::Static(static_config));
+	if let Some(active_time) = active_time {
+		if lead_time > active_time {
+			embassy_time::Timer::after(lead_time - active_time).await;
+		}
+	}
+
+	wake_and_ensure_connected(control, stack, config).await
 }
 
 /// Task to monitor PDP context and configure network stack.
@@ -167,11 +754,15 @@ pub async fn pdp_monitor_task(control: &'static Control<'static>, stack: &'stati
 	// Small delay after registration
 	embassy_time::Timer::after_millis(500).await;
 
+	// Last address the stack was configured with
+	let mut last_ip = None;
+
 	// Activate PDP context
 	match activate(control).await {
 		Ok(ip) => {
 			// Configure network stack
 			configure_stack(stack, ip, None);
+			last_ip = Some(ip);
 			PDP_STATUS_SIGNAL.signal(PdpStatus::Activated { ip });
 		}
 		Err(_) => {
@@ -188,7 +779,12 @@ pub async fn pdp_monitor_task(control: &'static Control<'static>, stack: &'stati
 			embassy_time::Timer::after_millis(500).await;
 			if let Some(ip) = get_ip_address(control).await {
 				configure_stack(stack, ip, None);
-				PDP_STATUS_SIGNAL.signal(PdpStatus::Activated { ip });
+				let status = PdpStatus::activated(last_ip, ip);
+				if matches!(status, PdpStatus::AddressChanged { .. }) {
+					crate::network::dns_cache_clear();
+				}
+				PDP_STATUS_SIGNAL.signal(status);
+				last_ip = Some(ip);
 			}
 		} else {
 			PDP_STATUS_SIGNAL.signal(PdpStatus::Deactivated);
@@ -197,11 +793,285 @@ pub async fn pdp_monitor_task(control: &'static Control<'static>, stack: &'stati
 }
 
 /// Wait for PDP context to be activated.
+///
+/// Also returns on `PdpStatus::AddressChanged`, which means the context is
+/// active again under a new address.
 pub async fn wait_for_activation() -> PdpStatus {
 	loop {
 		let status = PDP_STATUS_SIGNAL.wait().await;
-		if matches!(status, PdpStatus::Activated { .. }) {
+		if matches!(
+			status,
+			PdpStatus::Activated { .. } | PdpStatus::AddressChanged { .. }
+		) {
 			return status;
 		}
 	}
 }
+
+/// Pre-flight check before spending radio time on a transmission.
+///
+/// Passes when the modem is registered, the PDP context has an address and
+/// the signal (RSRP) is at least `config.min_rsrp_dbm`. Sending on a weak
+/// or missing link tends to fail halfway and waste energy.
+pub async fn is_good_enough_to_send(control: &Control<'_>, config: &Config) -> bool {
+	let registered = registration::query_registration(control)
+		.await
+		.is_some_and(|status| status.is_registered());
+	if !registered || get_ip_address(control).await.is_none() {
+		return false;
+	}
+
+	match crate::modem::get_signal_quality(control).await {
+		Ok(signal) => signal.rsrp_dbm >= config.min_rsrp_dbm,
+		Err(_) => false,
+	}
+}
+
+/// Wait until the device is fully online, using the SIM default APN.
+///
+/// See `wait_online_with_config`.
+pub async fn wait_online(
+	control: &Control<'_>,
+	stack: &Stack<'_>,
+	timeout: Duration,
+) -> Result<IpConfig> {
+	wait_online_with_config(control, stack, &PdpConfig::default(), timeout).await
+}
+
+/// Wait until the device is fully online.
+///
+/// Waits for network registration, activates the PDP context with
+/// `config`, configures the stack and waits for it to come up, all within
+/// a single `timeout` budget. This is the call to make before doing any
+/// network work.
+///
+/// # Returns
+/// The IP configuration, `Err(Error::Registration)` if registration is
+/// denied, `Err(Error::PdpActivation)` if the context could not be
+/// activated, or `Err(Error::Timeout)` if the device did not get online in
+/// time.
+pub async fn wait_online_with_config(
+	control: &Control<'_>,
+	stack: &Stack<'_>,
+	config: &PdpConfig<'_>,
+	timeout: Duration,
+) -> Result<IpConfig> {
+	wait_online_cancellable(control, stack, config, timeout, None).await
+}
+
+/// `wait_online_with_config` that can be aborted through `cancel`.
+///
+/// # Returns
+/// As `wait_online_with_config`, or `Err(Error::Cancelled)` if `cancel` is
+/// cancelled first.
+pub async fn wait_online_cancellable(
+	control: &Control<'_>,
+	stack: &Stack<'_>,
+	config: &PdpConfig<'_>,
+	timeout: Duration,
+	cancel: Option<&CancelToken>,
+) -> Result<IpConfig> {
+	let online = async {
+		let registered = registration::query_registration(control)
+			.await
+			.is_some_and(|status| status.is_registered());
+		if !registered {
+			registration::attach_outcome().await?;
+		}
+
+		let (ip, _) = activate_with_config(control, config).await?;
+
+		// The context details (DNS, gateway, MTU) fill in what CGPADDR lacks
+		let mut ip_config = get_context_config(control)
+			.await
+			.unwrap_or(IpConfig::from_address(ip));
+		ip_config.address = ip;
+		if ip_config.address_v6.is_none() {
+			ip_config.address_v6 = get_ip_addresses(control).await.ipv6;
+		}
+		configure_stack_with(stack, &ip_config);
+		crate::network::wait_for_config(stack).await;
+
+		Ok(ip_config)
+	};
+
+	with_timeout_cancellable(timeout, cancel, online).await?
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cgpaddr_dual_stack() {
+		let resp = "+CGPADDR: 0,\"10.0.0.130\",\"32.1.13.184.0.0.0.0.0.0.0.0.0.0.0.1\"\r\nOK\r\n";
+		let addresses = parse_cgpaddr(resp);
+
+		assert_eq!(addresses.ipv4, Some(Ipv4Address::new(10, 0, 0, 130)));
+		assert_eq!(addresses.ipv6, Some("2001:db8::1".parse().unwrap()));
+	}
+
+	#[test]
+	fn cgpaddr_single_stack() {
+		let resp = "+CGPADDR: 0,\"10.0.0.130\"\r\nOK\r\n";
+		assert_eq!(
+			parse_cgpaddr_response(resp),
+			Some(Ipv4Address::new(10, 0, 0, 130))
+		);
+
+		let resp = "+CGPADDR: 0,\"2001:DB8::1\"\r\nOK\r\n";
+		let addresses = parse_cgpaddr(resp);
+		assert_eq!(addresses.ipv4, None);
+		assert_eq!(addresses.ipv6, Some("2001:db8::1".parse().unwrap()));
+	}
+
+	#[test]
+	fn ipv4_surrounding_characters() {
+		let expected = Some(Ipv4Address::new(10, 0, 0, 2));
+		assert_eq!(parse_ipv4(" 10.0.0.2"), expected);
+		assert_eq!(parse_ipv4("10.0.0.2 "), expected);
+		assert_eq!(parse_ipv4("\"10.0.0.2\""), expected);
+		assert_eq!(parse_ipv4("[10.0.0.2]"), expected);
+	}
+
+	#[test]
+	fn ipv4_invalid() {
+		assert_eq!(parse_ipv4(""), None);
+		assert_eq!(parse_ipv4("10.0.0"), None);
+		assert_eq!(parse_ipv4("10.0.0.2.255"), None);
+		assert_eq!(parse_ipv4("10.0.0.256"), None);
+	}
+
+	#[test]
+	fn ipv6_notations() {
+		let expected: Ipv6Addr = "2001:db8::1".parse().unwrap();
+		assert_eq!(
+			parse_ipv6("32.1.13.184.0.0.0.0.0.0.0.0.0.0.0.1"),
+			Some(expected)
+		);
+		assert_eq!(parse_ipv6("2001:DB8:0:0:0:0:0:1"), Some(expected));
+		assert_eq!(parse_ipv6("[2001:db8::1]"), Some(expected));
+		assert_eq!(parse_ipv6("32.1.13.184"), None);
+		assert_eq!(parse_ipv6("32.1.13.184.0.0.0.0.0.0.0.0.0.0.0.1.0"), None);
+	}
+
+	#[test]
+	fn cgcontrdp_ipv4() {
+		// Single-stack response as laid out in the nRF91 AT command reference
+		let resp = "+CGCONTRDP: 0,,\"telenor.smart\",\"\",\"\",\"193.213.112.4\",\"130.67.15.198\",,,,,1358\r\nOK\r\n";
+		let config = parse_cgcontrdp(resp).unwrap();
+
+		assert_eq!(config.address, Ipv4Address::UNSPECIFIED);
+		assert_eq!(config.prefix_len, DEFAULT_PREFIX_LEN);
+		assert_eq!(config.gateway, None);
+		assert_eq!(
+			config.dns,
+			[
+				Some(Ipv4Address::new(193, 213, 112, 4)),
+				Some(Ipv4Address::new(130, 67, 15, 198))
+			]
+		);
+		assert_eq!(config.mtu, Some(1358));
+		assert_eq!(config.address_v6, None);
+	}
+
+	#[test]
+	fn cgcontrdp_local_address_and_gateway() {
+		let resp = "AT+CGCONTRDP=0\r\n\
+			+CGCONTRDP: 0,5,\"internet\",\"10.160.5.2.255.255.252.0\",\"10.160.5.1\",\"8.8.8.8\",\"\",,,,,1500\r\n\
+			OK\r\n";
+		let config = parse_cgcontrdp(resp).unwrap();
+
+		assert_eq!(config.address, Ipv4Address::new(10, 160, 5, 2));
+		assert_eq!(config.prefix_len, 22);
+		assert_eq!(config.gateway, Some(Ipv4Address::new(10, 160, 5, 1)));
+		assert_eq!(config.dns, [Some(Ipv4Address::new(8, 8, 8, 8)), None]);
+		assert_eq!(config.mtu, Some(1500));
+	}
+
+	#[test]
+	fn cgcontrdp_no_context() {
+		assert_eq!(parse_cgcontrdp("OK\r\n"), None);
+		assert_eq!(parse_cgcontrdp("ERROR\r\n"), None);
+	}
+
+	#[test]
+	fn cgcontrdp_dual_stack() {
+		let resp = "+CGCONTRDP: 0,,\"internet\",\"\",\"\",\"10.0.0.1\",\"10.0.0.2\",,,,,1500\r\n\
+			+CGCONTRDP: 0,,\"internet\",\"\",\"\",\"2001:4860:4860::8888\",\"2001:4860:4860::8844\",,,,,1280\r\n\
+			OK\r\n";
+		let config = parse_cgcontrdp(resp).unwrap();
+
+		assert_eq!(
+			config.dns,
+			[
+				Some(Ipv4Address::new(10, 0, 0, 1)),
+				Some(Ipv4Address::new(10, 0, 0, 2))
+			]
+		);
+		assert_eq!(config.mtu, Some(1500));
+		assert_eq!(
+			config.dns_v6,
+			[
+				Some("2001:4860:4860::8888".parse().unwrap()),
+				Some("2001:4860:4860::8844".parse().unwrap())
+			]
+		);
+		assert_eq!(config.address_v6, None);
+	}
+
+	#[test]
+	fn cgev_pdn_events() {
+		assert_eq!(
+			parse_cgev("+CGEV: ME PDN ACT 0,2"),
+			Some(PacketEvent::PdnActivated { cid: 0 })
+		);
+		assert_eq!(
+			parse_cgev("+CGEV: ME PDN DEACT 0"),
+			Some(PacketEvent::PdnDeactivated { cid: 0 })
+		);
+		assert_eq!(
+			parse_cgev("+CGEV: NW PDN DEACT 0\r\n"),
+			Some(PacketEvent::NwPdnDeactivated { cid: 0 })
+		);
+	}
+
+	#[test]
+	fn cgev_bearer_events() {
+		// <p_cid>,<cid>,<event_type>: the bearer is the second parameter
+		assert_eq!(
+			parse_cgev("+CGEV: NW ACT 0,1,2"),
+			Some(PacketEvent::BearerActivated { cid: 1 })
+		);
+		assert_eq!(
+			parse_cgev("+CGEV: ME DEACT 0,1,0"),
+			Some(PacketEvent::BearerDeactivated { cid: 1 })
+		);
+		assert_eq!(
+			parse_cgev("+CGEV: NW MODIFY 0,1,0"),
+			Some(PacketEvent::Modified { cid: 0 })
+		);
+		assert_eq!(
+			parse_cgev("+CGEV: IPV6 0"),
+			Some(PacketEvent::Ipv6LinkUp { cid: 0 })
+		);
+	}
+
+	#[test]
+	fn cgev_detach_and_restriction() {
+		assert_eq!(parse_cgev("+CGEV: NW DETACH"), Some(PacketEvent::Detached));
+		assert_eq!(parse_cgev("+CGEV: ME DETACH"), Some(PacketEvent::MeDetach));
+		assert_eq!(
+			parse_cgev("+CGEV: RESTR 1,2"),
+			Some(PacketEvent::Restricted)
+		);
+		assert_eq!(PacketEvent::Detached.cid(), None);
+	}
+
+	#[test]
+	fn cgev_unknown() {
+		assert_eq!(parse_cgev("+CGEV: ME SUSPEND 0"), None);
+		assert_eq!(parse_cgev("+CGEV: ME PDN ACT"), None);
+		assert_eq!(parse_cgev("+CEREG: 1"), None);
+	}
+}
@@ -5,9 +5,18 @@
 
 #![allow(dead_code)]
 
+use crate::cancel::{with_timeout_cancellable, CancelToken};
+use crate::error::{Error, Result};
+use crate::modem::{at_command_ok, at_response};
+
+use core::cell::RefCell;
+
 use embassy_net_nrf91::Control;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant};
+use heapless::{Deque, Vec};
 
 /// Network registration status from +CEREG responses.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -63,67 +72,342 @@ impl RegistrationStatus {
 /// allowing other tasks to await registration events.
 pub static REGISTRATION_SIGNAL: Signal<CriticalSectionRawMutex, RegistrationStatus> = Signal::new();
 
+/// Signal carrying the elapsed attach time each time the modem registers.
+///
+/// Measured from the moment CFUN=1 was issued, or from the last detach
+/// for subsequent reattaches.
+pub static ATTACH_TIME_SIGNAL: Signal<CriticalSectionRawMutex, Duration> = Signal::new();
+
+/// Shared connectivity statistics, updated by the registration monitor.
+static CONNECTIVITY_STATS: Mutex<CriticalSectionRawMutex, RefCell<ConnectivityStats>> =
+	Mutex::new(RefCell::new(ConnectivityStats::new()));
+
+/// Connectivity statistics collected across attach/detach cycles.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectivityStats {
+	attach_started: Option<Instant>,
+	last_attach_duration: Option<Duration>,
+	registered: bool,
+}
+
+impl ConnectivityStats {
+	/// Create empty statistics.
+	pub const fn new() -> Self {
+		Self {
+			attach_started: None,
+			last_attach_duration: None,
+			registered: false,
+		}
+	}
+
+	/// Time taken by the most recent attach, if one has completed.
+	pub fn last_attach_duration(&self) -> Option<Duration> {
+		self.last_attach_duration
+	}
+
+	/// Update statistics for a new registration status.
+	///
+	/// Returns the attach duration when this status completes an attach.
+	fn update(&mut self, status: RegistrationStatus, now: Instant) -> Option<Duration> {
+		if status.is_registered() == self.registered {
+			return None;
+		}
+		self.registered = status.is_registered();
+
+		if self.registered {
+			let duration = now - self.attach_started.take()?;
+			self.last_attach_duration = Some(duration);
+			Some(duration)
+		} else {
+			// Detached: time the reattach from here
+			self.attach_started = Some(now);
+			None
+		}
+	}
+}
+
+/// Get a snapshot of the current connectivity statistics.
+pub fn connectivity_stats() -> ConnectivityStats {
+	CONNECTIVITY_STATS.lock(|stats| *stats.borrow())
+}
+
+/// Record the start of an attach attempt.
+///
+/// Called when CFUN=1 is issued.
+pub fn mark_attach_start() {
+	CONNECTIVITY_STATS.lock(|stats| {
+		let mut stats = stats.borrow_mut();
+		stats.attach_started = Some(Instant::now());
+		stats.registered = false;
+	});
+}
+
+/// Number of registration transitions kept for diagnostics.
+pub const HISTORY_LEN: usize = 16;
+
+/// A registration status change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+	/// Status before the change
+	pub from: RegistrationStatus,
+	/// Status after the change
+	pub to: RegistrationStatus,
+	/// When the change was observed
+	pub at: Instant,
+}
+
+/// Ring of the most recent registration transitions, oldest first.
+static HISTORY: Mutex<CriticalSectionRawMutex, RefCell<Deque<Transition, HISTORY_LEN>>> =
+	Mutex::new(RefCell::new(Deque::new()));
+
+/// Get the most recent registration transitions, oldest first.
+///
+/// Transitions are recorded as the monitor observes them, so changes that
+/// happen and revert between two polls are not seen.
+pub fn registration_history() -> Vec<Transition, HISTORY_LEN> {
+	HISTORY.lock(|history| history.borrow().iter().copied().collect())
+}
+
+/// Append a transition, dropping the oldest if the ring is full.
+fn record_transition(transition: Transition) {
+	HISTORY.lock(|history| {
+		let mut history = history.borrow_mut();
+		if history.is_full() {
+			history.pop_front();
+		}
+		let _ = history.push_back(transition);
+	});
+}
+
+/// Feed a status change into the connectivity statistics and history.
+fn record_status(from: RegistrationStatus, status: RegistrationStatus) {
+	record_transition(Transition {
+		from,
+		to: status,
+		at: Instant::now(),
+	});
+
+	let attach_time =
+		CONNECTIVITY_STATS.lock(|stats| stats.borrow_mut().update(status, Instant::now()));
+
+	if let Some(duration) = attach_time {
+		ATTACH_TIME_SIGNAL.signal(duration);
+	}
+}
+
+/// CEREG unsolicited result code verbosity (`AT+CEREG=<n>`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CeregMode {
+	/// Registration status only: `+CEREG: <stat>`
+	Status = 1,
+	/// Registration status with location: `+CEREG: <stat>[,<tac>,<ci>,<AcT>]`
+	Location = 2,
+	/// Location plus reject cause and granted PSM timers:
+	/// `+CEREG: <stat>[,<tac>,<ci>,<AcT>[,<cause_type>,<reject_cause>[,<Active-Time>,<Periodic-TAU>]]]`
+	Psm = 5,
+}
+
+impl CeregMode {
+	/// AT command enabling this mode.
+	fn command(self) -> &'static str {
+		match self {
+			Self::Status => "AT+CEREG=1",
+			Self::Location => "AT+CEREG=2",
+			Self::Psm => "AT+CEREG=5",
+		}
+	}
+}
+
 /// Parse +CEREG response to extract registration status.
 ///
-/// Handles both query response format: `+CEREG: <n>,<stat>[,<tac>,<ci>,<AcT>]`
-/// and URC format: `+CEREG: <stat>[,<tac>,<ci>,<AcT>]`
+/// Assumes location mode (`AT+CEREG=2`). See `parse_cereg` for other modes.
 pub fn parse_cereg_response(response: &[u8]) -> Option<RegistrationStatus> {
-	let resp_str = core::str::from_utf8(response).ok()?;
-
-	// Find +CEREG: in the response
-	let cereg_pos = resp_str.find("+CEREG:")?;
-	let after_cereg = &resp_str[cereg_pos + 7..]; // Skip "+CEREG:"
+	parse_cereg(response, CeregMode::Location)
+}
 
-	// Skip whitespace
-	let trimmed = after_cereg.trim_start();
+/// Parse +CEREG response for the given CEREG mode.
+///
+/// Handles both query response format: `+CEREG: <n>,<stat>[,<tac>,<ci>,<AcT>]`
+/// and URC format: `+CEREG: <stat>[,<tac>,<ci>,<AcT>]`. Location fields are
+/// not expected in `CeregMode::Status`.
+pub fn parse_cereg(response: &[u8], mode: CeregMode) -> Option<RegistrationStatus> {
+	let line = cereg_line(response)?;
 
-	// Parse the numbers - could be "<n>,<stat>" or just "<stat>" for URC
-	let mut parts = trimmed.split(',');
-	let first = parts.next()?.trim();
+	let mut fields = line.split(',').map(str::trim);
+	let first = fields.next()?;
+	let second = fields.next();
 
-	// If there's a second part, first is <n> and second is <stat>
-	// If only one part, it's the <stat> (URC format)
-	let stat_str = if let Some(second) = parts.next() {
-		second.split_whitespace().next().unwrap_or(second.trim())
-	} else {
-		first.split_whitespace().next().unwrap_or(first)
+	let stat_str = match mode {
+		// Query is "<n>,<stat>", URC is just "<stat>"
+		CeregMode::Status => second.unwrap_or(first),
+		// Query has a numeric <stat> second, URC has a quoted <tac> second
+		CeregMode::Location | CeregMode::Psm => {
+			second.filter(|s| s.parse::<u8>().is_ok()).unwrap_or(first)
+		}
 	};
 
 	let stat: u8 = stat_str.parse().ok()?;
 	Some(RegistrationStatus::from_u8(stat))
 }
 
+/// Parse the PSM timers granted by the network from a +CEREG response.
+///
+/// Only present in `CeregMode::Psm` responses.
+///
+/// # Returns
+/// `(periodic_tau, active_time)`, or `None` if the timers are missing or
+/// PSM was not granted.
+pub fn parse_cereg_psm(response: &[u8]) -> Option<(Duration, Duration)> {
+	let line = cereg_line(response)?;
+
+	let mut fields: Vec<&str, 10> = Vec::new();
+	for field in line.split(',') {
+		fields.push(field.trim()).ok()?;
+	}
+
+	// Query responses have an extra leading <n> before the numeric <stat>
+	let offset = match fields.get(1) {
+		Some(field) if field.parse::<u8>().is_ok() => 1,
+		_ => 0,
+	};
+
+	let active_time = decode_active_time(fields.get(offset + 6)?)?;
+	let periodic_tau = decode_periodic_tau(fields.get(offset + 7)?)?;
+	Some((periodic_tau, active_time))
+}
+
+/// Extract the contents of the +CEREG line from a response.
+fn cereg_line(response: &[u8]) -> Option<&str> {
+	let resp_str = crate::modem::strip_echo(core::str::from_utf8(response).ok()?);
+	let cereg_pos = resp_str.find("+CEREG:")?;
+	Some(resp_str[cereg_pos + 7..].lines().next()?.trim()) // Skip "+CEREG:"
+}
+
+/// Parse a quoted 8-bit timer string such as `"00100001"`.
+fn parse_timer_bits(field: &str) -> Option<u8> {
+	u8::from_str_radix(field.trim_matches('"'), 2).ok()
+}
+
+/// Decode the Active-Time (T3324, GPRS Timer 2) value.
+pub(crate) fn decode_active_time(field: &str) -> Option<Duration> {
+	let bits = parse_timer_bits(field)?;
+	let value = u64::from(bits & 0x1f);
+	match bits >> 5 {
+		0b000 => Some(Duration::from_secs(value * 2)),
+		0b001 => Some(Duration::from_secs(value * 60)),
+		0b010 => Some(Duration::from_secs(value * 6 * 60)),
+		_ => None, // Deactivated
+	}
+}
+
+/// Decode the Periodic-TAU (T3412 extended, GPRS Timer 3) value.
+pub(crate) fn decode_periodic_tau(field: &str) -> Option<Duration> {
+	let bits = parse_timer_bits(field)?;
+	let value = u64::from(bits & 0x1f);
+	match bits >> 5 {
+		0b000 => Some(Duration::from_secs(value * 10 * 60)),
+		0b001 => Some(Duration::from_secs(value * 60 * 60)),
+		0b010 => Some(Duration::from_secs(value * 10 * 60 * 60)),
+		0b011 => Some(Duration::from_secs(value * 2)),
+		0b100 => Some(Duration::from_secs(value * 30)),
+		0b101 => Some(Duration::from_secs(value * 60)),
+		0b110 => Some(Duration::from_secs(value * 320 * 60 * 60)),
+		_ => None, // Deactivated
+	}
+}
+
+/// PSM timers granted by the network, as `(periodic_tau, active_time)`.
+static NEGOTIATED_PSM: Mutex<CriticalSectionRawMutex, RefCell<Option<(Duration, Duration)>>> =
+	Mutex::new(RefCell::new(None));
+
+/// Get the PSM timers granted by the network.
+///
+/// Updated on each registration query in `CeregMode::Psm` (the mode the
+/// registration monitor of `modem::init` uses) and by
+/// `modem::get_rf_status()`, and cleared when those report no granted
+/// timers. The granted values often differ from the requested ones.
+///
+/// # Returns
+/// `Some((periodic_tau, active_time))` while the network grants PSM.
+pub fn negotiated_psm() -> Option<(Duration, Duration)> {
+	NEGOTIATED_PSM.lock(|psm| *psm.borrow())
+}
+
+/// Store the PSM timers last reported by the modem, `None` if PSM is not
+/// granted.
+pub(crate) fn set_negotiated_psm(psm: Option<(Duration, Duration)>) {
+	NEGOTIATED_PSM.lock(|stored| *stored.borrow_mut() = psm);
+}
+
+/// Query the current registration status directly (`AT+CEREG?`).
+///
+/// Does not update the registration monitor or raise any signal.
+pub async fn query_registration(control: &Control<'_>) -> Option<RegistrationStatus> {
+	let response = at_response(control, "AT+CEREG?").await.ok()?;
+	// Query responses are always "<n>,<stat>", whatever the mode
+	parse_cereg(&response, CeregMode::Status)
+}
+
 /// Registration monitor that tracks CEREG status and signals on changes.
 pub struct RegistrationMonitor {
 	last_status: RegistrationStatus,
+	mode: CeregMode,
 }
 
 impl RegistrationMonitor {
-	/// Create a new registration monitor.
+	/// Create a new registration monitor using location mode.
 	pub fn new() -> Self {
+		Self::with_mode(CeregMode::Location)
+	}
+
+	/// Create a new registration monitor with the preferred CEREG mode.
+	pub fn with_mode(mode: CeregMode) -> Self {
 		Self {
 			last_status: RegistrationStatus::Unknown,
+			mode,
 		}
 	}
 
 	/// Enable CEREG unsolicited result codes on the modem.
 	///
-	/// Sends AT+CEREG=2 to enable URCs with location information.
-	pub async fn enable_urcs(&self, control: &Control<'_>) {
-		let mut resp_buf = [0u8; 128];
-		let _ = control.at_command(b"AT+CEREG=2", &mut resp_buf).await;
+	/// Tries the preferred mode first. If it is rejected, falls back to the
+	/// simpler modes in turn, down to AT+CEREG=1. The accepted mode is
+	/// remembered so responses are parsed accordingly.
+	///
+	/// # Returns
+	/// The mode that was accepted, or `Err(Error::AtCommand)` if none was.
+	pub async fn enable_urcs(&mut self, control: &Control<'_>) -> Result<CeregMode> {
+		for mode in [CeregMode::Psm, CeregMode::Location, CeregMode::Status] {
+			if (mode as u8) > (self.mode as u8) {
+				continue;
+			}
+			if at_command_ok(control, mode.command()).await.is_ok() {
+				self.mode = mode;
+				return Ok(mode);
+			}
+		}
+
+		Err(Error::AtCommand)
+	}
+
+	/// Get the CEREG mode currently in use.
+	pub fn mode(&self) -> CeregMode {
+		self.mode
 	}
 
 	/// Query current registration status and signal if changed.
 	///
 	/// Returns the current status.
 	pub async fn query_status(&mut self, control: &Control<'_>) -> RegistrationStatus {
-		let mut resp_buf = [0u8; 256];
-		let len = control.at_command(b"AT+CEREG?", &mut resp_buf).await;
+		if let Ok(response) = at_response(control, "AT+CEREG?").await {
+			// A response without timers means PSM is no longer granted
+			if self.mode == CeregMode::Psm {
+				set_negotiated_psm(parse_cereg_psm(&response));
+			}
 
-		if len > 0 {
-			if let Some(status) = parse_cereg_response(&resp_buf[..len]) {
+			if let Some(status) = parse_cereg(&response, self.mode) {
 				if status != self.last_status {
+					record_status(self.last_status, status);
 					self.last_status = status;
 					REGISTRATION_SIGNAL.signal(status);
 				}
@@ -161,9 +445,126 @@ pub async fn wait_for_registration() -> RegistrationStatus {
 	}
 }
 
+/// Wait for the outcome of a network attach.
+///
+/// Unlike `wait_for_registration`, this also reports failure: a denied
+/// registration or no registration within `timeout`.
+///
+/// # Returns
+/// `Ok(status)` once registered, `Err(Error::Registration)` if registration
+/// is denied, or `Err(Error::Timeout)` if neither happens within `timeout`.
+pub async fn await_attach(timeout: Duration) -> Result<RegistrationStatus> {
+	await_attach_cancellable(timeout, None).await
+}
+
+/// `await_attach` that can be aborted through `cancel`.
+///
+/// # Returns
+/// As `await_attach`, or `Err(Error::Cancelled)` if `cancel` is cancelled
+/// first.
+pub async fn await_attach_cancellable(
+	timeout: Duration,
+	cancel: Option<&CancelToken>,
+) -> Result<RegistrationStatus> {
+	with_timeout_cancellable(timeout, cancel, attach_outcome()).await?
+}
+
+/// `wait_for_registration` that can be aborted through `cancel`.
+///
+/// # Returns
+/// The registration status, or `Err(Error::Cancelled)` if `cancel` is
+/// cancelled first.
+pub async fn wait_for_registration_cancellable(cancel: &CancelToken) -> Result<RegistrationStatus> {
+	cancel.run(wait_for_registration()).await
+}
+
+/// Wait until registered or denied, without a time limit.
+pub(crate) async fn attach_outcome() -> Result<RegistrationStatus> {
+	loop {
+		let status = REGISTRATION_SIGNAL.wait().await;
+		if status.is_registered() {
+			return Ok(status);
+		}
+		if status == RegistrationStatus::Denied {
+			return Err(Error::Registration);
+		}
+	}
+}
+
 /// Wait for any registration status change.
 ///
 /// Returns the new status when it changes.
 pub async fn wait_for_status_change() -> RegistrationStatus {
 	REGISTRATION_SIGNAL.wait().await
 }
+
+/// Recovery for a modem stuck searching for a network.
+///
+/// Feed every registration status to `update()`. Once the modem has not
+/// been registered for `search_timeout`, `deadline()` passes and the
+/// caller runs `recover()`: the first recovery takes the modem offline and
+/// back, later ones also let it settle offline and check that it still
+/// answers (see `modem::cycle_functional_mode`). After `max_recoveries`
+/// recoveries without registering, or once the modem is hung, `recover()`
+/// gives up so the caller can escalate.
+#[derive(Clone, Debug)]
+pub struct SearchWatchdog {
+	search_timeout: Duration,
+	max_recoveries: u32,
+	recoveries: u32,
+	searching_since: Option<Instant>,
+}
+
+impl SearchWatchdog {
+	/// Create a watchdog allowing `search_timeout` per search and
+	/// `max_recoveries` recovery attempts.
+	pub const fn new(search_timeout: Duration, max_recoveries: u32) -> Self {
+		Self {
+			search_timeout,
+			max_recoveries,
+			recoveries: 0,
+			searching_since: None,
+		}
+	}
+
+	/// Record the current registration status.
+	pub fn update(&mut self, status: RegistrationStatus) {
+		if status.is_registered() {
+			self.recoveries = 0;
+			self.searching_since = None;
+		} else if self.searching_since.is_none() {
+			self.searching_since = Some(Instant::now());
+		}
+	}
+
+	/// Time at which recovery is due, or `None` while registered.
+	pub fn deadline(&self) -> Option<Instant> {
+		self.searching_since
+			.map(|since| since + self.search_timeout)
+	}
+
+	/// Number of recoveries since the modem was last registered.
+	pub fn recoveries(&self) -> u32 {
+		self.recoveries
+	}
+
+	/// Run the next recovery step and restart the search timer.
+	///
+	/// # Errors
+	/// Returns `Error::Registration` once `max_recoveries` recoveries have
+	/// not helped, or the error of the recovery step.
+	pub async fn recover(&mut self, control: &Control<'_>) -> Result<()> {
+		if self.recoveries >= self.max_recoveries {
+			return Err(Error::Registration);
+		}
+		self.recoveries += 1;
+		self.searching_since = Some(Instant::now());
+
+		if self.recoveries == 1 {
+			crate::modem::disable(control).await?;
+		} else {
+			crate::modem::cycle_functional_mode(control).await?;
+		}
+		crate::modem::enable(control).await
+	}
+}
@@ -0,0 +1,181 @@
+//! Periodic telemetry reports.
+//!
+//! `report_loop()` periodically gathers registration status, signal quality
+//! and modem measurements into a `Report`, encodes it and hands the payload
+//! to a user-supplied `ReportSink` (e.g. an MQTT publish or HTTP POST).
+//!
+//! Embassy tasks cannot be generic, so the application wraps
+//! `report_loop()` in its own task with its concrete sink type.
+//!
+//! ## Wire Format
+//! `Report::encode()` produces a fixed 10-byte little-endian layout:
+//!
+//! | Offset | Size | Field                                 |
+//! |--------|------|---------------------------------------|
+//! | 0      | 1    | Format version (1)                    |
+//! | 1      | 1    | Registration status (+CEREG `<stat>`) |
+//! | 2      | 1    | Flags: bit 0 signal, 1 vbat, 2 temp   |
+//! | 3      | 2    | RSRP (dBm, i16)                       |
+//! | 5      | 1    | RSRQ (dB, i8)                         |
+//! | 6      | 2    | Battery voltage (mV, u16)             |
+//! | 8      | 2    | Modem temperature (°C, i16)           |
+//!
+//! Fields whose flag is clear are zero and must be ignored.
+//!
+//! ## Error Handling
+//! Measurements that fail are left out of the report. Sink errors are
+//! ignored; the next report is sent at the next interval.
+
+#![allow(dead_code)]
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::modem::{self, SignalQuality};
+use crate::registration::{self, RegistrationStatus};
+
+use embassy_net_nrf91::Control;
+use embassy_time::Timer;
+
+/// Size of an encoded report.
+pub const REPORT_SIZE: usize = 10;
+
+/// Wire format version.
+const REPORT_VERSION: u8 = 1;
+
+const FLAG_SIGNAL: u8 = 1 << 0;
+const FLAG_VBAT: u8 = 1 << 1;
+const FLAG_TEMPERATURE: u8 = 1 << 2;
+
+/// Optional measurements to include in a report.
+///
+/// Registration status is always included. GNSS position is not supported
+/// yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReportFields {
+	/// Signal quality (`AT+CESQ`)
+	pub signal: bool,
+	/// Battery voltage (`AT%XVBAT`)
+	pub vbat: bool,
+	/// Modem temperature (`AT%XTEMP?`)
+	pub temperature: bool,
+}
+
+impl Default for ReportFields {
+	fn default() -> Self {
+		Self {
+			signal: true,
+			vbat: true,
+			temperature: false,
+		}
+	}
+}
+
+/// One telemetry snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Report {
+	/// Registration status
+	pub status: RegistrationStatus,
+	/// Signal quality, if requested and camped on a cell
+	pub signal: Option<SignalQuality>,
+	/// Battery voltage in millivolts, if requested
+	pub vbat_mv: Option<u16>,
+	/// Modem temperature in degrees Celsius, if requested
+	pub temperature: Option<i16>,
+}
+
+impl Report {
+	/// Gather a report from the modem.
+	pub async fn collect(control: &Control<'_>, fields: ReportFields) -> Self {
+		let status = registration::query_registration(control)
+			.await
+			.unwrap_or(RegistrationStatus::Unknown);
+
+		let signal = if fields.signal {
+			modem::get_signal_quality(control).await.ok()
+		} else {
+			None
+		};
+		let vbat_mv = if fields.vbat {
+			modem::get_vbat(control).await.ok()
+		} else {
+			None
+		};
+		let temperature = if fields.temperature {
+			modem::get_temperature(control).await.ok()
+		} else {
+			None
+		};
+
+		Self {
+			status,
+			signal,
+			vbat_mv,
+			temperature,
+		}
+	}
+
+	/// Encode the report in the fixed binary layout (see module docs).
+	///
+	/// # Returns
+	/// Number of bytes written (`REPORT_SIZE`).
+	///
+	/// # Errors
+	/// Returns `Error::Config` if `out` is shorter than `REPORT_SIZE`.
+	pub fn encode(&self, out: &mut [u8]) -> Result<usize> {
+		let out = out.get_mut(..REPORT_SIZE).ok_or(Error::Config)?;
+		out.fill(0);
+
+		out[0] = REPORT_VERSION;
+		out[1] = self.status as u8;
+		if let Some(signal) = self.signal {
+			out[2] |= FLAG_SIGNAL;
+			out[3..5].copy_from_slice(&signal.rsrp_dbm.to_le_bytes());
+			out[5] = signal.rsrq_db as u8;
+		}
+		if let Some(vbat_mv) = self.vbat_mv {
+			out[2] |= FLAG_VBAT;
+			out[6..8].copy_from_slice(&vbat_mv.to_le_bytes());
+		}
+		if let Some(temperature) = self.temperature {
+			out[2] |= FLAG_TEMPERATURE;
+			out[8..10].copy_from_slice(&temperature.to_le_bytes());
+		}
+
+		Ok(REPORT_SIZE)
+	}
+}
+
+/// Destination for encoded reports.
+#[allow(async_fn_in_trait)]
+pub trait ReportSink {
+	/// Deliver one encoded report.
+	async fn send(&mut self, payload: &[u8]) -> Result<()>;
+}
+
+/// Collect, encode and send a report every `config.report_interval`, forever.
+///
+/// A report is skipped when `pdp::is_good_enough_to_send` fails, rather
+/// than attempting it over a weak or missing link.
+///
+/// # Arguments
+/// * `control` - Modem control for the measurements
+/// * `config` - Report interval and send thresholds
+/// * `fields` - Optional measurements to include
+/// * `sink` - Where encoded reports are delivered
+pub async fn report_loop<S: ReportSink>(
+	control: &Control<'_>,
+	config: &Config,
+	fields: ReportFields,
+	sink: &mut S,
+) -> ! {
+	let mut payload = [0u8; REPORT_SIZE];
+	loop {
+		if crate::pdp::is_good_enough_to_send(control, config).await {
+			let report = Report::collect(control, fields).await;
+			if let Ok(len) = report.encode(&mut payload) {
+				let _ = sink.send(&payload[..len]).await;
+			}
+		}
+		Timer::after(config.report_interval).await;
+	}
+}
@@ -0,0 +1,93 @@
+//! Application core reset reason.
+//!
+//! Reads the POWER.RESETREAS register, which latches the cause of the last
+//! reset until cleared. There is no AT command for this; the modem only
+//! knows about its own resets.
+//!
+//! ## Limitations
+//! - Power-on and brownout resets leave no bit set and are both reported
+//!   as `ResetReason::PowerOn`.
+//! - A modem fault does not reset the application core; use
+//!   `modem::is_faulted()` for that.
+
+#![allow(dead_code)]
+
+use embassy_nrf::pac;
+
+/// RESETREAS bits (nRF91 series).
+const RESETREAS_RESETPIN: u32 = 1 << 0;
+const RESETREAS_DOG: u32 = 1 << 1;
+const RESETREAS_OFF: u32 = 1 << 2;
+const RESETREAS_DIF: u32 = 1 << 3;
+const RESETREAS_SREQ: u32 = 1 << 4;
+const RESETREAS_LOCKUP: u32 = 1 << 5;
+const RESETREAS_CTRLAP: u32 = 1 << 6;
+
+/// Cause of the last application core reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetReason {
+	/// Power-on or brownout (no reset reason latched)
+	PowerOn,
+	/// Reset pin
+	PinReset,
+	/// Watchdog timeout
+	Watchdog,
+	/// Software reset (`SCB::sys_reset`, e.g. after a fatal error)
+	SoftReset,
+	/// CPU lockup
+	Lockup,
+	/// Wake-up from System OFF
+	Wakeup,
+	/// Debugger (debug interface mode or CTRL-AP)
+	Debug,
+}
+
+impl ResetReason {
+	/// Decode the raw RESETREAS value.
+	///
+	/// If several bits are set, the most specific cause wins.
+	pub fn from_bits(bits: u32) -> Self {
+		if bits & RESETREAS_DOG != 0 {
+			ResetReason::Watchdog
+		} else if bits & RESETREAS_LOCKUP != 0 {
+			ResetReason::Lockup
+		} else if bits & RESETREAS_SREQ != 0 {
+			ResetReason::SoftReset
+		} else if bits & RESETREAS_OFF != 0 {
+			ResetReason::Wakeup
+		} else if bits & (RESETREAS_DIF | RESETREAS_CTRLAP) != 0 {
+			ResetReason::Debug
+		} else if bits & RESETREAS_RESETPIN != 0 {
+			ResetReason::PinReset
+		} else {
+			ResetReason::PowerOn
+		}
+	}
+
+	/// Get reason as a string.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ResetReason::PowerOn => "Power-on",
+			ResetReason::PinReset => "Reset pin",
+			ResetReason::Watchdog => "Watchdog",
+			ResetReason::SoftReset => "Soft reset",
+			ResetReason::Lockup => "CPU lockup",
+			ResetReason::Wakeup => "Wake from System OFF",
+			ResetReason::Debug => "Debugger",
+		}
+	}
+}
+
+/// Read and clear the reset reason.
+///
+/// Call once at boot. The register is cleared afterwards so that a later
+/// power-on reset is not mistaken for the previous cause.
+///
+/// # Returns
+/// The decoded reason and the raw register value.
+pub fn take_reset_reason() -> (ResetReason, u32) {
+	let bits = pac::POWER.resetreas().read().0;
+	// Write-one-to-clear
+	pac::POWER.resetreas().write(|w| w.0 = bits);
+	(ResetReason::from_bits(bits), bits)
+}
@@ -0,0 +1,109 @@
+//! Retry with modem recovery for recoverable failures.
+//!
+//! An unattended device should not halt on a transient modem hiccup.
+//! `RetryPolicy::run()` retries an operation (modem enable, registration,
+//! PDP activation) with a modem functional mode cycle and a backoff delay
+//! between attempts, and only gives up on errors the policy classifies as
+//! fatal, after an optional attempt limit, or when the modem stops
+//! answering. `fatal_error!` is then reserved for what retrying cannot fix,
+//! such as missing modem firmware or a hung modem.
+//!
+//! ## Error Handling
+//! `run()` returns the last error once the policy gives up.
+
+#![allow(dead_code)]
+
+use crate::backoff::Backoff;
+use crate::error::{Error, Result};
+use crate::modem;
+
+use core::future::Future;
+
+use embassy_net_nrf91::Control;
+use embassy_time::Timer;
+
+/// Default fatal classification: errors a functional mode cycle cannot fix.
+///
+/// `ModemInit` (missing or broken modem firmware), `TaskSpawn` and
+/// `Config` are fatal; everything else is retried.
+pub fn is_fatal_default(error: Error) -> bool {
+	matches!(error, Error::ModemInit | Error::TaskSpawn | Error::Config)
+}
+
+/// Policy for retrying recoverable failures.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+	backoff: Backoff,
+	max_attempts: Option<u32>,
+	is_fatal: fn(Error) -> bool,
+}
+
+impl RetryPolicy {
+	/// Retry forever with `backoff` between attempts, using
+	/// `is_fatal_default`.
+	pub const fn new(backoff: Backoff) -> Self {
+		Self {
+			backoff,
+			max_attempts: None,
+			is_fatal: is_fatal_default,
+		}
+	}
+
+	/// Give up after `attempts` attempts (at least 1).
+	pub const fn with_max_attempts(mut self, attempts: u32) -> Self {
+		self.max_attempts = Some(if attempts == 0 { 1 } else { attempts });
+		self
+	}
+
+	/// Set which errors are fatal and returned without retrying.
+	pub const fn with_fatal(mut self, is_fatal: fn(Error) -> bool) -> Self {
+		self.is_fatal = is_fatal;
+		self
+	}
+
+	/// Run `op` until it succeeds, taking the modem offline between
+	/// attempts.
+	///
+	/// After a failed attempt the functional mode is cycled (see
+	/// `modem::cycle_functional_mode`) and the next backoff delay is awaited,
+	/// so `op` must bring the modem back up itself, e.g. starting with
+	/// `modem::enable`. The backoff starts over after a success.
+	///
+	/// # Returns
+	/// The result of the first successful attempt.
+	///
+	/// # Errors
+	/// Returns the error of the last attempt if it is fatal or the attempt
+	/// limit is reached, or `Error::AtTimeout` if the modem no longer
+	/// answers, since retrying cannot bring a hung modem back.
+	pub async fn run<T, F, Fut>(&mut self, control: &Control<'_>, mut op: F) -> Result<T>
+	where
+		F: FnMut() -> Fut,
+		Fut: Future<Output = Result<T>>,
+	{
+		let mut attempts = 0;
+		loop {
+			attempts += 1;
+			let error = match op().await {
+				Ok(value) => {
+					self.backoff.reset();
+					return Ok(value);
+				}
+				Err(e) => e,
+			};
+			if (self.is_fatal)(error)
+				|| self.max_attempts.is_some_and(|max| attempts >= max)
+			{
+				return Err(error);
+			}
+
+			// Start the next attempt from a clean modem state
+			if let Err(e @ Error::AtTimeout(_)) =
+				modem::cycle_functional_mode(control).await
+			{
+				return Err(e);
+			}
+			Timer::after(self.backoff.next_delay()).await;
+		}
+	}
+}
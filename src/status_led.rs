@@ -0,0 +1,76 @@
+//! Status LED indication.
+//!
+//! The LED is driven by `status_led_task`, so callers only report state
+//! through `set_pattern()` and never wait on GPIO timing. Patterns are
+//! delivered through a signal: if several arrive while one is playing,
+//! only the latest is shown, which rate-limits rapid registration flapping.
+
+#![allow(dead_code)]
+
+use embassy_nrf::gpio::Output;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Timer};
+
+/// Pattern shown on the status LED.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LedPattern {
+	/// LED off
+	Off,
+	/// Three quick blinks at boot
+	Startup,
+	/// One short blink, e.g. on a registration change
+	Blink,
+	/// Double blink repeating every two seconds until changed: no SIM
+	NoSim,
+}
+
+/// Signal carrying the next pattern to show.
+static LED_SIGNAL: Signal<CriticalSectionRawMutex, LedPattern> = Signal::new();
+
+/// Show `pattern` on the status LED.
+pub fn set_pattern(pattern: LedPattern) {
+	LED_SIGNAL.signal(pattern);
+}
+
+/// Task driving the status LED.
+#[embassy_executor::task]
+pub async fn status_led_task(mut led: Output<'static>) {
+	let mut pattern = LedPattern::Off;
+	loop {
+		match pattern {
+			LedPattern::Off => {
+				led.set_low();
+				pattern = LED_SIGNAL.wait().await;
+			}
+			LedPattern::Startup => {
+				blink(&mut led, 3, 100).await;
+				pattern = LedPattern::Off;
+			}
+			LedPattern::Blink => {
+				blink(&mut led, 1, 100).await;
+				pattern = LedPattern::Off;
+			}
+			LedPattern::NoSim => {
+				blink(&mut led, 2, 150).await;
+				// Repeat until a new pattern arrives
+				if let Ok(next) =
+					with_timeout(Duration::from_secs(2), LED_SIGNAL.wait())
+						.await
+				{
+					pattern = next;
+				}
+			}
+		}
+	}
+}
+
+/// Blink `count` times with equal on/off times.
+async fn blink(led: &mut Output<'static>, count: u8, ms: u64) {
+	for _ in 0..count {
+		led.set_high();
+		Timer::after(Duration::from_millis(ms)).await;
+		led.set_low();
+		Timer::after(Duration::from_millis(ms)).await;
+	}
+}
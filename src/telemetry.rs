@@ -0,0 +1,174 @@
+//! CBOR and JSON encoding of telemetry reports.
+//!
+//! `encode()` writes a `Report` as a CBOR map with small integer keys,
+//! which the backend can decode with any CBOR library. Absent measurements
+//! are left out of the map.
+//!
+//! | Key | Field                            | Type |
+//! |-----|----------------------------------|------|
+//! | 0   | Registration status (`<stat>`)   | uint |
+//! | 1   | RSRP (dBm)                       | int  |
+//! | 2   | RSRQ (dB)                        | int  |
+//! | 3   | Battery voltage (mV)             | uint |
+//! | 4   | Modem temperature (°C)           | int  |
+//!
+//! ## JSON
+//! `encode_json()` writes the same report as a JSON object for plain REST
+//! endpoints, e.g.
+//! `{"status":1,"registration":"Registered (home network)","rsrp":-95,"rsrq":-11,"vbat_mv":3700}`.
+//! Keys are `status`, `registration`, `rsrp`, `rsrq`, `vbat_mv` and
+//! `temperature`; absent measurements are left out.
+//!
+//! ## Error Handling
+//! `encode()` and `encode_json()` return `Error::Config` if the output
+//! buffer is too small.
+
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+use crate::report::Report;
+
+use core::fmt::{self, Write as _};
+
+use minicbor::encode::write::Cursor;
+use minicbor::Encoder;
+
+/// Map keys.
+const KEY_STATUS: u8 = 0;
+const KEY_RSRP: u8 = 1;
+const KEY_RSRQ: u8 = 2;
+const KEY_VBAT: u8 = 3;
+const KEY_TEMPERATURE: u8 = 4;
+
+/// Largest encoded report, for sizing output buffers.
+pub const MAX_ENCODED_SIZE: usize = 24;
+
+/// Encode a report as a CBOR map.
+///
+/// # Returns
+/// Number of bytes written to `out`.
+///
+/// # Errors
+/// Returns `Error::Config` if `out` is too small.
+pub fn encode(report: &Report, out: &mut [u8]) -> Result<usize> {
+	let entries = 1
+		+ 2 * u64::from(report.signal.is_some())
+		+ u64::from(report.vbat_mv.is_some())
+		+ u64::from(report.temperature.is_some());
+
+	let mut encoder = Encoder::new(Cursor::new(out));
+	encode_map(&mut encoder, report, entries).map_err(|_| Error::Config)?;
+	Ok(encoder.into_writer().position())
+}
+
+/// Write the map, failing on the first write that does not fit.
+fn encode_map(
+	e: &mut Encoder<Cursor<&mut [u8]>>,
+	report: &Report,
+	entries: u64,
+) -> core::result::Result<(), minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+	e.map(entries)?.u8(KEY_STATUS)?.u8(report.status as u8)?;
+	if let Some(signal) = report.signal {
+		e.u8(KEY_RSRP)?.i16(signal.rsrp_dbm)?;
+		e.u8(KEY_RSRQ)?.i8(signal.rsrq_db)?;
+	}
+	if let Some(vbat_mv) = report.vbat_mv {
+		e.u8(KEY_VBAT)?.u16(vbat_mv)?;
+	}
+	if let Some(temperature) = report.temperature {
+		e.u8(KEY_TEMPERATURE)?.i16(temperature)?;
+	}
+	Ok(())
+}
+
+/// Largest JSON-encoded report, for sizing output buffers.
+pub const MAX_JSON_SIZE: usize = 128;
+
+/// Encode a report as a JSON object.
+///
+/// # Returns
+/// Number of bytes written to `out`.
+///
+/// # Errors
+/// Returns `Error::Config` if `out` is too small.
+pub fn encode_json(report: &Report, out: &mut [u8]) -> Result<usize> {
+	let mut json = JsonWriter {
+		out,
+		len: 0,
+		fields: 0,
+	};
+	encode_object(&mut json, report).map_err(|_| Error::Config)?;
+	Ok(json.len)
+}
+
+/// Write the object, failing on the first write that does not fit.
+fn encode_object(json: &mut JsonWriter<'_>, report: &Report) -> fmt::Result {
+	json.write_char('{')?;
+	json.key("status")?;
+	write!(json, "{}", report.status as u8)?;
+	json.key("registration")?;
+	json.string(report.status.as_str())?;
+	if let Some(signal) = report.signal {
+		json.key("rsrp")?;
+		write!(json, "{}", signal.rsrp_dbm)?;
+		json.key("rsrq")?;
+		write!(json, "{}", signal.rsrq_db)?;
+	}
+	if let Some(vbat_mv) = report.vbat_mv {
+		json.key("vbat_mv")?;
+		write!(json, "{}", vbat_mv)?;
+	}
+	if let Some(temperature) = report.temperature {
+		json.key("temperature")?;
+		write!(json, "{}", temperature)?;
+	}
+	json.write_char('}')
+}
+
+/// Minimal JSON writer into a fixed buffer.
+struct JsonWriter<'a> {
+	out: &'a mut [u8],
+	len: usize,
+	/// Fields written to the current object, for comma placement
+	fields: usize,
+}
+
+impl JsonWriter<'_> {
+	/// Write an object key and the following colon.
+	fn key(&mut self, key: &str) -> fmt::Result {
+		if self.fields > 0 {
+			self.write_char(',')?;
+		}
+		self.fields += 1;
+		self.string(key)?;
+		self.write_char(':')
+	}
+
+	/// Write a quoted, escaped string.
+	fn string(&mut self, value: &str) -> fmt::Result {
+		self.write_char('"')?;
+		for c in value.chars() {
+			match c {
+				'"' => self.write_str("\\\"")?,
+				'\\' => self.write_str("\\\\")?,
+				'\n' => self.write_str("\\n")?,
+				'\r' => self.write_str("\\r")?,
+				'\t' => self.write_str("\\t")?,
+				c if c < ' ' => write!(self, "\\u{:04x}", c as u32)?,
+				c => self.write_char(c)?,
+			}
+		}
+		self.write_char('"')
+	}
+}
+
+impl fmt::Write for JsonWriter<'_> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let end = self.len + s.len();
+		self.out.get_mut(self.len..end)
+			.ok_or(fmt::Error)?
+			.copy_from_slice(s.as_bytes());
+		self.len = end;
+		Ok(())
+	}
+}